@@ -0,0 +1,81 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use size_of::{Context, SizeOf};
+use std::{collections::BTreeSet, sync::Arc};
+
+const SIZES: [usize; 3] = [1_000, 100_000, 1_000_000];
+
+/// Compares deduplicating pointers with a `Context` to the `BTreeSet` it
+/// previously used
+fn dedup_pointers(c: &mut Criterion) {
+    let mut group = c.benchmark_group("dedup_pointers");
+
+    for size in SIZES {
+        // Every pointer is seen twice, like a graph where each node has two
+        // owners
+        let arcs: Vec<Arc<u64>> = (0..size as u64).map(Arc::new).collect();
+        let pointers: Vec<*const u64> = arcs.iter().chain(arcs.iter()).map(Arc::as_ptr).collect();
+
+        group.bench_with_input(
+            BenchmarkId::new("BTreeSet", size),
+            &pointers,
+            |b, pointers| {
+                b.iter(|| {
+                    let mut set = BTreeSet::new();
+                    for &ptr in pointers {
+                        black_box(set.insert(ptr as usize));
+                    }
+                    set
+                });
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("Context", size),
+            &pointers,
+            |b, pointers| {
+                b.iter(|| {
+                    let mut context = Context::new();
+                    for &ptr in pointers {
+                        black_box(context.insert_ptr(ptr));
+                    }
+                    context
+                });
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("Context::with_pointer_capacity", size),
+            &pointers,
+            |b, pointers| {
+                b.iter(|| {
+                    let mut context = Context::with_pointer_capacity(size);
+                    for &ptr in pointers {
+                        black_box(context.insert_ptr(ptr));
+                    }
+                    context
+                });
+            },
+        );
+    }
+
+    group.finish();
+}
+
+/// Measures a collection of shared values
+fn size_of_arcs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("size_of_arcs");
+
+    for size in SIZES {
+        let arcs: Vec<Arc<u64>> = (0..size as u64).map(Arc::new).collect();
+        let shared = (arcs.clone(), arcs);
+
+        group.bench_with_input(BenchmarkId::from_parameter(size), &shared, |b, shared| {
+            b.iter(|| shared.size_of());
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, dedup_pointers, size_of_arcs);
+criterion_main!(benches);
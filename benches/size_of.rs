@@ -0,0 +1,38 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use size_of::SizeOf;
+#[cfg(not(feature = "derive"))]
+use size_of_derive::SizeOf;
+
+const SIZES: [usize; 3] = [1_000, 100_000, 5_000_000];
+
+#[derive(SizeOf)]
+struct Entry {
+    a: String,
+    b: Option<Box<u64>>,
+    c: u64,
+}
+
+/// Measures a collection of derived values with a default context, which
+/// doesn't record trees, types, sinks or budgets
+fn size_of_entries(c: &mut Criterion) {
+    let mut group = c.benchmark_group("size_of_entries");
+
+    for size in SIZES {
+        let entries: Vec<Entry> = (0..size as u64)
+            .map(|idx| Entry {
+                a: idx.to_string(),
+                b: (idx % 2 == 0).then(|| Box::new(idx)),
+                c: idx,
+            })
+            .collect();
+
+        group.bench_with_input(BenchmarkId::from_parameter(size), &entries, |b, entries| {
+            b.iter(|| entries.size_of());
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, size_of_entries);
+criterion_main!(benches);
@@ -2,9 +2,9 @@ use proc_macro2::{Span, TokenStream};
 use quote::{format_ident, quote};
 use std::{mem::replace, ops::Not};
 use syn::{
-    parse_macro_input, parse_quote_spanned, Attribute, Data, DeriveInput, Error, Fields, Generics,
-    Index, Lit, Meta, NestedMeta, Path, Result, ReturnType, Type, TypeArray, TypeBareFn, TypePtr,
-    TypeReference, TypeSlice, TypeTuple, Variant, WherePredicate,
+    ext::IdentExt, parse_macro_input, parse_quote_spanned, Attribute, Data, DeriveInput, Error,
+    Field, Fields, Generics, Ident, Index, Lit, Meta, NestedMeta, Path, Result, ReturnType, Type,
+    TypeArray, TypeBareFn, TypePtr, TypeReference, TypeSlice, TypeTuple, Variant, WherePredicate,
 };
 
 /// Derives the `SizeOf` trait for the given item
@@ -12,6 +12,13 @@ use syn::{
 /// Works on structs and enums, `SizeOf` must be implemented manually for unions
 /// unless the `#[size_of(skip_all)]` top-level attribute is used
 ///
+/// Every unskipped field and variant is recorded under its own name within
+/// the reports produced by `SizeOf::size_of_tree()`, tuple fields are named by
+/// their index
+///
+/// `SizeOf::IS_CHILDLESS` is also implemented, a type is childless when all
+/// of its unskipped fields are
+///
 /// Accepted attributes:
 /// - `#[size_of(crate = "<crate_path>")]` allows setting the path to the
 ///   `size_of` crate, this is only allowed at the top level
@@ -102,6 +109,7 @@ fn size_of_derive_inner(input: DeriveInput) -> Result<TokenStream> {
     if has_skip_all(&input.attrs) {
         let type_name = &input.ident;
         let (intro_generics, fwd_generics, where_clause) = input.generics.split_for_impl();
+        let is_childless = childless_const(&crate_path, &[]);
 
         return Ok(quote! {
             #[automatically_derived]
@@ -110,6 +118,8 @@ fn size_of_derive_inner(input: DeriveInput) -> Result<TokenStream> {
             {
                 #[inline]
                 fn size_of_children(&self, context: &mut #crate_path::Context) {}
+
+                #is_childless
             }
         });
 
@@ -147,6 +157,9 @@ fn size_of_derive_inner(input: DeriveInput) -> Result<TokenStream> {
                 TokenStream::new()
             };
 
+            let is_childless =
+                childless_const(&crate_path, &childless_types(structure.fields.iter()));
+
             Ok(quote! {
                 #[automatically_derived]
                 impl #intro_generics #crate_path::SizeOf for #struct_name #fwd_generics
@@ -156,6 +169,8 @@ fn size_of_derive_inner(input: DeriveInput) -> Result<TokenStream> {
                     fn size_of_children(&self, context: &mut #crate_path::Context) {
                         #(#field_sizes;)*
                     }
+
+                    #is_childless
                 }
             })
         }
@@ -201,16 +216,22 @@ fn size_of_derive_inner(input: DeriveInput) -> Result<TokenStream> {
                                 TokenStream::new()
                             } else {
                                 let ident = &field.ident;
+                                let name = field_name(field.ident.as_ref().unwrap());
                                 let ty = normalize_type(field.ty.clone());
                                 quote! {
-                                    <#ty as #crate_path::SizeOf>::size_of_children(#ident, context);
+                                    context.named(#name, |context| {
+                                        <#ty as #crate_path::SizeOf>::size_of_children(#ident, context);
+                                    });
                                 }
                             }
                         });
 
+                        let name = field_name(variant_name);
                         quote! {
                             Self::#variant_name { #(#idents,)* #ellipses } => {
-                                #(#body)*
+                                context.named(#name, |context| {
+                                    #(#body)*
+                                });
                             }
                         }
                     }
@@ -230,16 +251,22 @@ fn size_of_derive_inner(input: DeriveInput) -> Result<TokenStream> {
                                 TokenStream::new()
                             } else {
                                 let ident = format_ident!("_{idx}");
+                                let name = idx.to_string();
                                 let ty = normalize_type(field.ty.clone());
                                 quote! {
-                                    <#ty as #crate_path::SizeOf>::size_of_children(#ident, context);
+                                    context.named(#name, |context| {
+                                        <#ty as #crate_path::SizeOf>::size_of_children(#ident, context);
+                                    });
                                 }
                             }
                         });
 
+                        let name = field_name(variant_name);
                         quote! {
                             Self::#variant_name(#(#idents),*) => {
-                                #(#body)*
+                                context.named(#name, |context| {
+                                    #(#body)*
+                                });
                             }
                         }
                     }
@@ -275,6 +302,17 @@ fn size_of_derive_inner(input: DeriveInput) -> Result<TokenStream> {
                 }
             };
 
+            let is_childless = childless_const(
+                &crate_path,
+                &childless_types(
+                    enumeration
+                        .variants
+                        .iter()
+                        .filter(|variant| !has_skip(&variant.attrs))
+                        .flat_map(|variant| variant.fields.iter()),
+                ),
+            );
+
             Ok(quote! {
                 #[automatically_derived]
                 impl #intro_generics #crate_path::SizeOf for #tuple_name #fwd_generics
@@ -283,6 +321,8 @@ fn size_of_derive_inner(input: DeriveInput) -> Result<TokenStream> {
                     fn size_of_children(&self, context: &mut #crate_path::Context) {
                         #body
                     }
+
+                    #is_childless
                 }
             })
         }
@@ -298,6 +338,7 @@ fn size_of_derive_inner(input: DeriveInput) -> Result<TokenStream> {
 /// Returns `true` if the number of given variants that:
 /// - Are not skipped
 /// - Have at least one unskipped field
+///
 /// Is greater than zero
 fn all_variants_empty<'a, I>(mut variants: I) -> bool
 where
@@ -342,8 +383,11 @@ fn collect_field_info(crate_path: &Path, fields: &Fields) -> (Vec<Type>, Vec<Tok
                         None
                     } else {
                         let field_ident = &field.ident;
+                        let field_name = field_name(field.ident.as_ref().unwrap());
                         let field_size = quote! {
-                            <#field_type as #crate_path::SizeOf>::size_of_children(&self.#field_ident, context)
+                            context.named(#field_name, |context| {
+                                <#field_type as #crate_path::SizeOf>::size_of_children(&self.#field_ident, context);
+                            })
                         };
 
                         Some((field_type, field_size))
@@ -360,9 +404,12 @@ fn collect_field_info(crate_path: &Path, fields: &Fields) -> (Vec<Type>, Vec<Tok
                 .enumerate()
                 .flat_map(|(idx, field)| has_skip(&field.attrs).not().then(|| {
                     let field_type = normalize_type(field.ty.clone());
+                    let field_name = idx.to_string();
                     let idx = Index::from(idx);
                     let field_size = quote! {
-                        <#field_type as #crate_path::SizeOf>::size_of_children(&self.#idx, context)
+                        context.named(#field_name, |context| {
+                            <#field_type as #crate_path::SizeOf>::size_of_children(&self.#idx, context);
+                        })
                     };
 
                     (field_type, field_size)
@@ -381,6 +428,35 @@ fn collect_field_info(crate_path: &Path, fields: &Fields) -> (Vec<Type>, Vec<Tok
     (field_types, field_sizes)
 }
 
+/// Returns the types of all unskipped fields that could have children
+fn childless_types<'a, I>(fields: I) -> Vec<Type>
+where
+    I: Iterator<Item = &'a Field> + 'a,
+{
+    let mut types: Vec<_> = fields
+        .filter(|field| !has_skip(&field.attrs))
+        .map(|field| normalize_type(field.ty.clone()))
+        .filter(|ty| !is_trivial_bound(ty))
+        .collect();
+    dedup_types(&mut types);
+
+    types
+}
+
+/// Creates an implementation of `SizeOf::IS_CHILDLESS` which is `true` when
+/// all of the given types are childless
+fn childless_const(crate_path: &Path, types: &[Type]) -> TokenStream {
+    let value = if types.is_empty() {
+        quote! { true }
+    } else {
+        quote! { #(<#types as #crate_path::SizeOf>::IS_CHILDLESS)&&* }
+    };
+
+    quote! {
+        const IS_CHILDLESS: bool = #value;
+    }
+}
+
 /// Returns `true` if the type is trivial elidible
 ///
 /// Currently returns `true` for the following types:
@@ -395,6 +471,12 @@ fn is_trivial_bound(ty: &Type) -> bool {
     matches!(ty, Type::BareFn(_) | Type::Ptr(_))
 }
 
+/// Returns the name of a field or variant as it'll appear within size reports,
+/// e.g. `r#type` becomes `"type"`
+fn field_name(ident: &Ident) -> String {
+    ident.unraw().to_string()
+}
+
 /// Normalizes a type
 ///
 /// Currently just removes all parenthesis from around the type
@@ -0,0 +1,149 @@
+//! Models of allocator size classes
+//!
+//! Allocators often round allocation sizes up to their internal size classes,
+//! meaning that a `Box<[u8; 4000]>` can really take up 4096 bytes of memory.
+//! Setting an [`AllocatorModel`] on a [`Context`](crate::Context) makes it
+//! record the difference between requested and allocated bytes as overhead
+
+use core::{fmt::Debug, mem::size_of};
+
+/// A model of an allocator, used to figure out how many bytes it actually
+/// reserves for an allocation
+pub trait AllocatorModel: Debug + Send + Sync {
+    /// Returns the number of bytes the allocator reserves for an allocation of
+    /// `size` bytes, this should never be less than `size`
+    fn usable_size(&self, size: usize) -> usize;
+}
+
+/// Rounds `size` up to the next multiple of `align`, `align` must be a power
+/// of two
+#[inline]
+const fn round_up(size: usize, align: usize) -> usize {
+    match size.checked_add(align - 1) {
+        Some(size) => size & !(align - 1),
+        None => size,
+    }
+}
+
+/// A model of glibc's `malloc()`
+///
+/// Allocations are made in chunks with a single `usize` of overhead which are
+/// aligned to two `usize`s (16 bytes on 32-bit x86, where glibc aligns chunks
+/// for `long double`), allocations over the default mmap threshold of 128
+/// KiB are made directly with `mmap()` and are rounded up to a page. The mmap
+/// threshold is dynamic within glibc, so this is only an estimate for large
+/// allocations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct GlibcMalloc;
+
+impl GlibcMalloc {
+    // https://sourceware.org/glibc/wiki/MallocInternals
+    const SIZE_SZ: usize = size_of::<usize>();
+    const MALLOC_ALIGNMENT: usize = if cfg!(target_arch = "x86") {
+        16
+    } else {
+        2 * Self::SIZE_SZ
+    };
+    const MIN_CHUNK_SIZE: usize = 4 * Self::SIZE_SZ;
+    const MMAP_THRESHOLD: usize = 128 * 1024;
+    const PAGE_SIZE: usize = 4096;
+}
+
+impl AllocatorModel for GlibcMalloc {
+    fn usable_size(&self, size: usize) -> usize {
+        // Sizes this large can't be allocated in the first place
+        let Some(with_header) = size.checked_add(Self::SIZE_SZ) else {
+            return size;
+        };
+
+        if size >= Self::MMAP_THRESHOLD {
+            // mmapped chunks have a two `usize` header and are rounded up to a
+            // full page
+            round_up(with_header, Self::PAGE_SIZE)
+                .saturating_sub(2 * Self::SIZE_SZ)
+                .max(size)
+        } else {
+            let chunk = round_up(with_header, Self::MALLOC_ALIGNMENT);
+            let chunk = if chunk < Self::MIN_CHUNK_SIZE {
+                Self::MIN_CHUNK_SIZE
+            } else {
+                chunk
+            };
+
+            chunk - Self::SIZE_SZ
+        }
+    }
+}
+
+/// A model of [jemalloc](https://jemalloc.net/jemalloc.3.html#size_classes)
+///
+/// Small allocations are rounded up to multiples of 16 bytes, after 128 bytes
+/// every doubling of size is split into four evenly spaced size classes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Jemalloc;
+
+impl AllocatorModel for Jemalloc {
+    fn usable_size(&self, size: usize) -> usize {
+        if size <= 8 {
+            8
+        } else if size <= 128 {
+            round_up(size, 16)
+        } else {
+            match size.checked_next_power_of_two() {
+                Some(power) => round_up(size, power / 8),
+                None => size,
+            }
+        }
+    }
+}
+
+/// A model of [mimalloc](https://github.com/microsoft/mimalloc)
+///
+/// Allocations of up to 8 words are rounded to a whole word, after that every
+/// doubling of size is split into four evenly spaced size bins. Allocations
+/// over 128 KiB are rounded up to the operating system's allocation
+/// granularity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Mimalloc;
+
+impl Mimalloc {
+    const WORD_SIZE: usize = size_of::<usize>();
+    // https://github.com/microsoft/mimalloc/blob/v2.0.9/include/mimalloc-types.h#L161
+    const MEDIUM_OBJ_SIZE_MAX: usize = 128 * 1024;
+}
+
+impl AllocatorModel for Mimalloc {
+    // https://github.com/microsoft/mimalloc/blob/v2.0.9/src/page-queue.c#L57-L98
+    fn usable_size(&self, size: usize) -> usize {
+        if size > Self::MEDIUM_OBJ_SIZE_MAX {
+            // https://github.com/microsoft/mimalloc/blob/v2.0.9/src/os.c#L188-L201
+            let align = if size < 512 * 1024 {
+                4 * 1024
+            } else if size < 2 * 1024 * 1024 {
+                64 * 1024
+            } else if size < 8 * 1024 * 1024 {
+                256 * 1024
+            } else if size < 32 * 1024 * 1024 {
+                1024 * 1024
+            } else {
+                4 * 1024 * 1024
+            };
+
+            return round_up(size, align);
+        }
+
+        let words = size.div_ceil(Self::WORD_SIZE);
+        let words = if words <= 8 {
+            if words == 0 {
+                1
+            } else {
+                words
+            }
+        } else {
+            let bit = (usize::BITS - 1 - (words - 1).leading_zeros()) as usize;
+            round_up(words, 1 << (bit - 2))
+        };
+
+        words * Self::WORD_SIZE
+    }
+}
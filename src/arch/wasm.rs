@@ -1,6 +1,6 @@
-#![cfg(target_arch = "wasm")]
+#![cfg(target_arch = "wasm32")]
 
-use core::arch::wasm::v128;
+use core::arch::wasm32::v128;
 
 impl_total_size_childless! {
     v128,
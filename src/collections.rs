@@ -11,9 +11,9 @@ impl SizeOf for String {
     #[inline]
     fn size_of_children(&self, context: &mut Context) {
         if self.capacity() != 0 {
-            context
-                .add_vectorlike(self.len(), self.capacity(), size_of::<u8>())
-                .add_distinct_allocation();
+            context.attribute_to::<Self, _>(|ctx| {
+                ctx.add_vectorlike_allocation(self.len(), self.capacity(), size_of::<u8>());
+            });
         }
     }
 }
@@ -22,9 +22,9 @@ impl SizeOf for CString {
     fn size_of_children(&self, context: &mut Context) {
         let length = self.to_bytes_with_nul().len();
         if length != 0 {
-            context
-                .add_arraylike(length, size_of::<u8>())
-                .add_distinct_allocation();
+            context.attribute_to::<Self, _>(|ctx| {
+                ctx.add_arraylike_allocation(length, size_of::<u8>());
+            });
         }
     }
 }
@@ -35,15 +35,32 @@ where
 {
     #[inline]
     fn size_of_children(&self, context: &mut Context) {
-        if self.capacity() != 0 {
-            if size_of::<T>() != 0 {
-                context
-                    .add_vectorlike(self.len(), self.capacity(), size_of::<T>())
-                    .add_distinct_allocation();
-            }
+        size_of_vec(self, context, |ctx| {
+            ctx.visit_elements(self, |ctx, element| element.size_of_children(ctx));
+        });
+    }
+}
 
-            self.as_slice().size_of_children(context);
-        }
+/// Adds the allocation of `vec` to the context, its elements are visited with
+/// `visit_elements` if they can have children
+#[inline]
+pub(crate) fn size_of_vec<T, F>(vec: &Vec<T>, context: &mut Context, visit_elements: F)
+where
+    T: SizeOf,
+    F: FnOnce(&mut Context),
+{
+    if vec.capacity() != 0 {
+        context.attribute_to::<Vec<T>, _>(|ctx| {
+            ctx.named("Vec", |ctx| {
+                if size_of::<T>() != 0 {
+                    ctx.add_vectorlike_allocation(vec.len(), vec.capacity(), size_of::<T>());
+                }
+
+                if !T::IS_CHILDLESS {
+                    visit_elements(ctx);
+                }
+            });
+        });
     }
 }
 
@@ -53,15 +70,17 @@ where
 {
     fn size_of_children(&self, context: &mut Context) {
         if self.capacity() != 0 {
-            if size_of::<T>() != 0 {
-                context
-                    .add_vectorlike(self.len(), self.capacity(), size_of::<T>())
-                    .add_distinct_allocation();
-            }
-
-            let (left, right) = self.as_slices();
-            left.size_of_children(context);
-            right.size_of_children(context);
+            context.attribute_to::<Self, _>(|ctx| {
+                ctx.named("VecDeque", |ctx| {
+                    if size_of::<T>() != 0 {
+                        ctx.add_vectorlike_allocation(self.len(), self.capacity(), size_of::<T>());
+                    }
+
+                    if !T::IS_CHILDLESS {
+                        ctx.visit_elements(self, |ctx, element| element.size_of_children(ctx));
+                    }
+                });
+            });
         }
     }
 }
@@ -72,14 +91,17 @@ where
 {
     fn size_of_children(&self, context: &mut Context) {
         if self.capacity() != 0 {
-            if size_of::<T>() != 0 {
-                context
-                    .add_vectorlike(self.len(), self.capacity(), size_of::<T>())
-                    .add_distinct_allocation();
-            }
-
-            self.iter()
-                .for_each(|element| element.size_of_children(context));
+            context.attribute_to::<Self, _>(|ctx| {
+                ctx.named("BinaryHeap", |ctx| {
+                    if size_of::<T>() != 0 {
+                        ctx.add_vectorlike_allocation(self.len(), self.capacity(), size_of::<T>());
+                    }
+
+                    if !T::IS_CHILDLESS {
+                        ctx.visit_elements(self, |ctx, element| element.size_of_children(ctx));
+                    }
+                });
+            });
         }
     }
 }
@@ -92,13 +114,20 @@ where
         let length = self.len();
 
         if length != 0 {
-            // Record each node as a `{ T, *const (), *const () }`
-            context
-                .add_arraylike(length, size_of::<T>() + (size_of::<*const ()>() * 2))
-                .add_distinct_allocations(length);
-
-            self.iter()
-                .for_each(|element| element.size_of_children(context));
+            context.attribute_to::<Self, _>(|ctx| {
+                ctx.named("LinkedList", |ctx| {
+                    // Record each node as a `{ T, *const (), *const () }`
+                    let node_size = size_of::<T>() + (size_of::<*const ()>() * 2);
+                    ctx.estimated(|ctx| {
+                        ctx.add_arraylike(length, node_size)
+                            .add_allocations(length, node_size);
+                    });
+
+                    if !T::IS_CHILDLESS {
+                        ctx.visit_elements(self, |ctx, element| element.size_of_children(ctx));
+                    }
+                });
+            });
         }
     }
 }
@@ -132,8 +161,11 @@ pub(crate) mod btree {
 
     // TODO: Figure out the unused capacity as well
     // TODO: Estimate the number of allocated buckets each btree makes
-    pub(crate) const fn estimate_btree_size<K, V>(length: usize) -> usize {
-        length * size_of::<FakeNode<K, V>>() * 2 / (BTREE_MAX + BTREE_MIN)
+    pub(crate) const fn estimate_btree_size<K, V>(length: usize) -> Option<usize> {
+        match length.checked_mul(size_of::<FakeNode<K, V>>() * 2) {
+            Some(size) => Some(size / (BTREE_MAX + BTREE_MIN)),
+            None => None,
+        }
     }
 
     impl<K> SizeOf for BTreeSet<K>
@@ -142,12 +174,20 @@ pub(crate) mod btree {
     {
         fn size_of_children(&self, context: &mut Context) {
             if !self.is_empty() {
-                context
-                    .add(estimate_btree_size::<K, ()>(self.len()))
-                    // FIXME: Estimate the number of allocated buckets
-                    .add_distinct_allocation();
-
-                self.iter().for_each(|key| key.size_of_children(context));
+                context.attribute_to::<Self, _>(|ctx| {
+                    ctx.named("BTreeSet", |ctx| {
+                        let size = ctx.saturate(estimate_btree_size::<K, ()>(self.len()));
+                        ctx.estimated(|ctx| {
+                            ctx.add(size)
+                                // FIXME: Estimate the number of allocated buckets
+                                .add_allocation(size);
+                        });
+
+                        if !K::IS_CHILDLESS {
+                            ctx.visit_elements(self, |ctx, key| key.size_of_children(ctx));
+                        }
+                    });
+                });
             }
         }
     }
@@ -159,14 +199,22 @@ pub(crate) mod btree {
     {
         fn size_of_children(&self, context: &mut Context) {
             if !self.is_empty() {
-                context
-                    .add(estimate_btree_size::<K, V>(self.len()))
-                    // FIXME: Estimate the number of allocated buckets
-                    .add_distinct_allocation();
-
-                self.iter().for_each(|(key, value)| {
-                    key.size_of_children(context);
-                    value.size_of_children(context);
+                context.attribute_to::<Self, _>(|ctx| {
+                    ctx.named("BTreeMap", |ctx| {
+                        let size = ctx.saturate(estimate_btree_size::<K, V>(self.len()));
+                        ctx.estimated(|ctx| {
+                            ctx.add(size)
+                                // FIXME: Estimate the number of allocated buckets
+                                .add_allocation(size);
+                        });
+
+                        if !(K::IS_CHILDLESS && V::IS_CHILDLESS) {
+                            ctx.visit_elements(self, |ctx, (key, value)| {
+                                ctx.named("keys", |ctx| key.size_of_children(ctx))
+                                    .named("values", |ctx| value.size_of_children(ctx));
+                            });
+                        }
+                    });
                 });
             }
         }
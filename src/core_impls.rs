@@ -38,6 +38,8 @@ where
     fn size_of_children(&self, context: &mut Context) {
         self.as_slice().size_of_children(context);
     }
+
+    const IS_CHILDLESS: bool = T::IS_CHILDLESS;
 }
 
 impl<T> SizeOf for [T]
@@ -46,9 +48,13 @@ where
 {
     #[inline]
     fn size_of_children(&self, context: &mut Context) {
-        self.iter()
-            .for_each(|element| element.size_of_children(context));
+        // Skip visiting every element when they can't have any children
+        if !T::IS_CHILDLESS {
+            context.visit_inline_elements(self, |ctx, element| element.size_of_children(ctx));
+        }
     }
+
+    const IS_CHILDLESS: bool = T::IS_CHILDLESS;
 }
 
 impl<T> SizeOf for ManuallyDrop<T>
@@ -59,6 +65,8 @@ where
     fn size_of_children(&self, context: &mut Context) {
         self.deref().size_of_children(context);
     }
+
+    const IS_CHILDLESS: bool = T::IS_CHILDLESS;
 }
 
 impl<T> SizeOf for Option<T>
@@ -71,6 +79,8 @@ where
             inner.size_of_children(context);
         }
     }
+
+    const IS_CHILDLESS: bool = T::IS_CHILDLESS;
 }
 
 impl<T, E> SizeOf for Result<T, E>
@@ -85,6 +95,8 @@ where
             Err(err) => err.size_of_children(context),
         }
     }
+
+    const IS_CHILDLESS: bool = T::IS_CHILDLESS && E::IS_CHILDLESS;
 }
 
 impl<T> SizeOf for Reverse<T>
@@ -95,6 +107,8 @@ where
     fn size_of_children(&self, context: &mut Context) {
         self.0.size_of_children(context);
     }
+
+    const IS_CHILDLESS: bool = T::IS_CHILDLESS;
 }
 
 // TODO: Is there a better impl for this?
@@ -117,6 +131,8 @@ where
     fn size_of_children(&self, context: &mut Context) {
         self.get().size_of_children(context);
     }
+
+    const IS_CHILDLESS: bool = T::IS_CHILDLESS;
 }
 
 impl<T> SizeOf for RefCell<T>
@@ -125,9 +141,12 @@ where
 {
     #[inline]
     fn size_of_children(&self, context: &mut Context) {
-        // Ignore any errors that occur while trying to borrow a RefCell
-        if let Ok(cell) = self.try_borrow() {
-            cell.deref().size_of_children(context);
+        // Mutably borrowed cells can't be measured
+        match self.try_borrow() {
+            Ok(cell) => cell.deref().size_of_children(context),
+            Err(_) => {
+                context.add_unmeasured();
+            }
         }
     }
 }
@@ -148,6 +167,8 @@ where
     fn size_of_children(&self, context: &mut Context) {
         self.0.size_of_children(context);
     }
+
+    const IS_CHILDLESS: bool = T::IS_CHILDLESS;
 }
 
 impl<C, B> SizeOf for ControlFlow<C, B>
@@ -162,6 +183,8 @@ where
             Self::Break(brk) => brk.size_of_children(context),
         }
     }
+
+    const IS_CHILDLESS: bool = C::IS_CHILDLESS && B::IS_CHILDLESS;
 }
 
 impl<'a, T> SizeOf for Cow<'a, T>
@@ -190,6 +213,8 @@ where
             ready.size_of_children(context);
         }
     }
+
+    const IS_CHILDLESS: bool = T::IS_CHILDLESS;
 }
 
 impl<T> SizeOf for AssertUnwindSafe<T>
@@ -200,15 +225,23 @@ where
     fn size_of_children(&self, context: &mut Context) {
         self.0.size_of_children(context);
     }
+
+    const IS_CHILDLESS: bool = T::IS_CHILDLESS;
 }
 
 impl SizeOf for Arguments<'_> {
     #[inline]
     fn size_of_children(&self, _context: &mut Context) {}
+
+    const IS_CHILDLESS: bool = true;
 }
 
 impl_total_size_childless! {
     str,
+    CStr,
+}
+
+impl_total_size_childless! {
     bool,
     char,
 
@@ -255,7 +288,6 @@ impl_total_size_childless! {
     NonZeroUsize,
     NonZeroIsize,
 
-    CStr,
     Layout,
     TypeId,
     Duration,
@@ -299,11 +331,17 @@ impl_tuple! {
 impl_function_ptrs! {
     "C",
     "Rust",
+    "system",
+    #[cfg(target_arch = "arm")]
     "aapcs",
+    #[cfg(target_arch = "x86")]
     "cdecl",
-    "win64",
-    "sysv64",
-    "system",
+    #[cfg(target_arch = "x86")]
     "stdcall",
+    #[cfg(target_arch = "x86")]
     "fastcall",
+    #[cfg(target_arch = "x86_64")]
+    "win64",
+    #[cfg(target_arch = "x86_64")]
+    "sysv64",
 }
@@ -0,0 +1,417 @@
+use crate::{Context, HumanBytes, SizeOf, TotalSize};
+use alloc::{
+    borrow::Cow,
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{
+    fmt::{self, Display},
+    slice,
+};
+
+/// The change between two measurements of the same value, e.g. before and
+/// after running a workload
+///
+/// Unlike subtracting two [`TotalSize`]s every count has a signed delta, so
+/// both growth and shrinkage can be seen
+///
+/// ```rust
+/// use size_of::{SizeDiff, SizeOf};
+///
+/// let mut buffer = Vec::<u8>::with_capacity(16);
+/// let before = buffer.size_of();
+/// buffer.reserve_exact(1024);
+///
+/// let diff = SizeDiff::new(before, buffer.size_of());
+/// assert_eq!(diff.total_bytes(), 1024 - 16);
+/// assert_eq!(diff.distinct_allocations(), 0);
+/// assert!(diff.grew());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SizeDiff {
+    before: TotalSize,
+    after: TotalSize,
+}
+
+impl SizeDiff {
+    /// Creates the difference from `before` to `after`
+    #[inline]
+    pub const fn new(before: TotalSize, after: TotalSize) -> Self {
+        Self { before, after }
+    }
+
+    /// Returns the size before the change
+    #[inline]
+    pub const fn before(&self) -> TotalSize {
+        self.before
+    }
+
+    /// Returns the size after the change
+    #[inline]
+    pub const fn after(&self) -> TotalSize {
+        self.after
+    }
+
+    /// Returns the change in total bytes, see [`TotalSize::total_bytes()`]
+    #[inline]
+    pub const fn total_bytes(&self) -> isize {
+        delta(self.before.total_bytes(), self.after.total_bytes())
+    }
+
+    /// Returns the change in excess bytes, see [`TotalSize::excess_bytes()`]
+    #[inline]
+    pub const fn excess_bytes(&self) -> isize {
+        delta(self.before.excess_bytes(), self.after.excess_bytes())
+    }
+
+    /// Returns the change in shared bytes, see [`TotalSize::shared_bytes()`]
+    #[inline]
+    pub const fn shared_bytes(&self) -> isize {
+        delta(self.before.shared_bytes(), self.after.shared_bytes())
+    }
+
+    /// Returns the change in distinct allocations, see
+    /// [`TotalSize::distinct_allocations()`]
+    #[inline]
+    pub const fn distinct_allocations(&self) -> isize {
+        delta(
+            self.before.distinct_allocations(),
+            self.after.distinct_allocations(),
+        )
+    }
+
+    /// Returns the change in allocator overhead, see
+    /// [`TotalSize::overhead_bytes()`]
+    #[inline]
+    pub const fn overhead_bytes(&self) -> isize {
+        delta(self.before.overhead_bytes(), self.after.overhead_bytes())
+    }
+
+    /// Returns the change in borrowed bytes, see
+    /// [`TotalSize::borrowed_bytes()`]
+    #[inline]
+    pub const fn borrowed_bytes(&self) -> isize {
+        delta(self.before.borrowed_bytes(), self.after.borrowed_bytes())
+    }
+
+    /// Returns the change in estimated bytes, see
+    /// [`TotalSize::estimated_bytes()`]
+    #[inline]
+    pub const fn estimated_bytes(&self) -> isize {
+        delta(self.before.estimated_bytes(), self.after.estimated_bytes())
+    }
+
+    /// Returns the change in inline bytes, see [`TotalSize::inline_bytes()`]
+    #[inline]
+    pub const fn inline_bytes(&self) -> isize {
+        delta(self.before.inline_bytes(), self.after.inline_bytes())
+    }
+
+    /// Returns the change in heap bytes, see [`TotalSize::heap_bytes()`]
+    #[inline]
+    pub const fn heap_bytes(&self) -> isize {
+        delta(self.before.heap_bytes(), self.after.heap_bytes())
+    }
+
+    /// Returns the change in used bytes, see [`TotalSize::used_bytes()`]
+    #[inline]
+    pub const fn used_bytes(&self) -> isize {
+        delta(self.before.used_bytes(), self.after.used_bytes())
+    }
+
+    /// Returns the change in unmeasured values, see
+    /// [`TotalSize::unmeasured_values()`]
+    #[inline]
+    pub const fn unmeasured_values(&self) -> isize {
+        delta(
+            self.before.unmeasured_values(),
+            self.after.unmeasured_values(),
+        )
+    }
+
+    /// Returns the change in truncated values, see
+    /// [`TotalSize::truncated_values()`]
+    #[inline]
+    pub const fn truncated_values(&self) -> isize {
+        delta(
+            self.before.truncated_values(),
+            self.after.truncated_values(),
+        )
+    }
+
+    /// Returns `true` if the total bytes increased
+    #[inline]
+    pub const fn grew(&self) -> bool {
+        self.after.total_bytes() > self.before.total_bytes()
+    }
+
+    /// Returns `true` if the total bytes decreased
+    #[inline]
+    pub const fn shrank(&self) -> bool {
+        self.after.total_bytes() < self.before.total_bytes()
+    }
+
+    /// Returns `true` if none of the counts changed
+    #[inline]
+    pub fn is_unchanged(&self) -> bool {
+        self.before == self.after
+    }
+}
+
+impl Display for SizeDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({} -> {}), {:+} allocations",
+            signed_bytes(self.total_bytes()),
+            HumanBytes::from(self.before.total_bytes()),
+            HumanBytes::from(self.after.total_bytes()),
+            self.distinct_allocations(),
+        )
+    }
+}
+
+impl SizeOf for SizeDiff {
+    #[inline]
+    fn size_of_children(&self, _context: &mut Context) {}
+
+    const IS_CHILDLESS: bool = true;
+}
+
+/// The changes between two sets of named measurements, e.g. two
+/// [`RetainedReport`](crate::RetainedReport)s, see
+/// [`RetainedReport::diff()`](crate::RetainedReport::diff)
+///
+/// Entries are sorted so that the biggest changes in total bytes come first,
+/// entries only within one of the sets are compared to a size of zero
+///
+/// ```rust
+/// use size_of::{DiffReport, TotalSize};
+///
+/// let before = [("cache", TotalSize::total(1024)), ("queue", TotalSize::total(64))];
+/// let after = [("cache", TotalSize::total(4096)), ("index", TotalSize::total(16))];
+///
+/// let report = DiffReport::new(before, after);
+/// assert_eq!(report.iter().next().unwrap().name(), "cache");
+/// assert_eq!(report.get("queue").unwrap().diff().total_bytes(), -64);
+/// assert_eq!(report.total().total_bytes(), 4096 + 16 - 1024 - 64);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DiffReport {
+    entries: Vec<DiffEntry>,
+}
+
+impl DiffReport {
+    /// Creates a new report from the named measurements before and after the
+    /// change, measurements with the same name are added together
+    pub fn new<B, A, N, M>(before: B, after: A) -> Self
+    where
+        B: IntoIterator<Item = (N, TotalSize)>,
+        A: IntoIterator<Item = (M, TotalSize)>,
+        N: Into<Cow<'static, str>>,
+        M: Into<Cow<'static, str>>,
+    {
+        let mut sizes: BTreeMap<Cow<'static, str>, (TotalSize, TotalSize)> = BTreeMap::new();
+        for (name, size) in before {
+            sizes.entry(name.into()).or_default().0 += size;
+        }
+        for (name, size) in after {
+            sizes.entry(name.into()).or_default().1 += size;
+        }
+
+        let mut entries: Vec<_> = sizes
+            .into_iter()
+            .map(|(name, (before, after))| DiffEntry::new(name, SizeDiff::new(before, after)))
+            .collect();
+        entries.sort_by(|a, b| {
+            b.diff
+                .total_bytes()
+                .unsigned_abs()
+                .cmp(&a.diff.total_bytes().unsigned_abs())
+                .then_with(|| a.name.cmp(&b.name))
+        });
+
+        Self { entries }
+    }
+
+    /// Returns the number of entries within the report
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the report contains no entries
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the entry with the given name (if it exists)
+    #[inline]
+    pub fn get(&self, name: &str) -> Option<&DiffEntry> {
+        self.entries.iter().find(|entry| entry.name() == name)
+    }
+
+    /// Returns an iterator over all entries within the report, the biggest
+    /// changes come first
+    #[inline]
+    pub fn iter(&self) -> slice::Iter<'_, DiffEntry> {
+        self.entries.iter()
+    }
+
+    /// Returns an iterator over the entries whose total bytes increased
+    #[inline]
+    pub fn grown(&self) -> impl Iterator<Item = &DiffEntry> + '_ {
+        self.entries.iter().filter(|entry| entry.diff.grew())
+    }
+
+    /// Returns an iterator over the entries whose total bytes decreased
+    #[inline]
+    pub fn shrunk(&self) -> impl Iterator<Item = &DiffEntry> + '_ {
+        self.entries.iter().filter(|entry| entry.diff.shrank())
+    }
+
+    /// Returns the change of all entries combined
+    pub fn total(&self) -> SizeDiff {
+        let (before, after) = self.entries.iter().fold(
+            (TotalSize::zero(), TotalSize::zero()),
+            |(before, after), entry| (before + entry.diff.before, after + entry.diff.after),
+        );
+
+        SizeDiff::new(before, after)
+    }
+}
+
+impl<'a> IntoIterator for &'a DiffReport {
+    type Item = &'a DiffEntry;
+    type IntoIter = slice::Iter<'a, DiffEntry>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl Display for DiffReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>12} {:>12} {:>12} {:>12}  name",
+            "change", "before", "after", "allocations",
+        )?;
+
+        // Unchanged entries are only counted since they're not interesting
+        let mut unchanged = 0;
+        for entry in &self.entries {
+            if entry.diff.is_unchanged() {
+                unchanged += 1;
+                continue;
+            }
+
+            writeln!(
+                f,
+                "{:>12} {:>12} {:>12} {:>+12}  {}",
+                signed_bytes(entry.diff.total_bytes()),
+                HumanBytes::from(entry.diff.before.total_bytes()).to_string(),
+                HumanBytes::from(entry.diff.after.total_bytes()).to_string(),
+                entry.diff.distinct_allocations(),
+                entry.name,
+            )?;
+        }
+
+        let total = self.total();
+        writeln!(
+            f,
+            "{:>12} {:>12} {:>12} {:>+12}  total",
+            signed_bytes(total.total_bytes()),
+            HumanBytes::from(total.before.total_bytes()).to_string(),
+            HumanBytes::from(total.after.total_bytes()).to_string(),
+            total.distinct_allocations(),
+        )?;
+        if unchanged != 0 {
+            writeln!(f, "{unchanged} unchanged")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl SizeOf for DiffReport {
+    fn size_of_children(&self, context: &mut Context) {
+        self.entries.size_of_children(context);
+    }
+}
+
+/// The change of a single named measurement within a [`DiffReport`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DiffEntry {
+    name: Cow<'static, str>,
+    diff: SizeDiff,
+}
+
+impl DiffEntry {
+    /// Creates a new `DiffEntry`
+    #[inline]
+    pub fn new<N>(name: N, diff: SizeDiff) -> Self
+    where
+        N: Into<Cow<'static, str>>,
+    {
+        Self {
+            name: name.into(),
+            diff,
+        }
+    }
+
+    /// Returns the name of the measurement
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the change of the measurement
+    #[inline]
+    pub const fn diff(&self) -> SizeDiff {
+        self.diff
+    }
+}
+
+impl SizeOf for DiffEntry {
+    fn size_of_children(&self, context: &mut Context) {
+        self.name.size_of_children(context);
+    }
+}
+
+/// Returns `after - before`, saturating at the bounds of an `isize`
+const fn delta(before: usize, after: usize) -> isize {
+    if after >= before {
+        let grown = after - before;
+        if grown > isize::MAX as usize {
+            isize::MAX
+        } else {
+            grown as isize
+        }
+    } else {
+        let shrunk = before - after;
+        if shrunk > isize::MAX as usize {
+            isize::MIN
+        } else {
+            -(shrunk as isize)
+        }
+    }
+}
+
+/// Formats a change in bytes with its sign, e.g. `+1.50 KiB` or `-8 B`
+fn signed_bytes(delta: isize) -> String {
+    let sign = match delta {
+        delta if delta > 0 => "+",
+        delta if delta < 0 => "-",
+        _ => "",
+    };
+
+    format!("{sign}{}", HumanBytes::from(delta.unsigned_abs()))
+}
@@ -0,0 +1,284 @@
+use crate::{HumanBytes, SizeSink, TotalSize};
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec::Vec,
+};
+use core::fmt::{self, Display, Write};
+
+/// The maximum number of nodes a [`DotSink`] records by default
+const DEFAULT_MAX_NODES: usize = 1000;
+
+/// A [`SizeSink`] that records the ownership graph of everything a
+/// [`Context`](crate::Context) measures, which can be written as a
+/// [Graphviz](https://graphviz.org) DOT graph with its [`Display`] impl
+///
+/// Every node is an allocation labelled with the type that owns it and the
+/// bytes the type directly owns, every measured value is a node as well.
+/// Edges point from owners to the allocations they own, so the allocations of
+/// `Rc`s and `Arc`s that are seen more than once are shared nodes with an edge
+/// from each of their owners
+///
+/// Once the graph holds its maximum number of nodes any further allocations
+/// are merged into their owners, see [`DotSink::with_max_nodes()`]
+///
+/// ```rust
+/// use size_of::{Context, DotSink, SizeOf};
+/// use std::sync::Arc;
+///
+/// let shared = Arc::new(String::from("shared"));
+/// let value = (shared.clone(), vec![shared]);
+///
+/// let mut context = Context::new();
+/// context.add_sink(DotSink::new());
+/// value.size_of_with_context(&mut context);
+///
+/// let graph = context.remove_sink::<DotSink>().unwrap();
+/// assert_eq!(graph.shared_nodes(), 1);
+/// assert!(graph.to_string().starts_with("digraph ownership {"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct DotSink {
+    /// The maximum number of nodes recorded
+    max_nodes: usize,
+    nodes: Vec<DotNode>,
+    /// The edges between nodes as `(owner, owned)` pairs
+    edges: BTreeSet<(usize, usize)>,
+    /// The node of every shared allocation by its address
+    shared: BTreeMap<usize, usize>,
+    /// The currently entered types and shared allocations
+    frames: Vec<Frame>,
+    /// The number of allocations merged into their owners
+    omitted: usize,
+}
+
+impl DotSink {
+    /// Creates a new sink that records at most 1000 nodes
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_max_nodes(DEFAULT_MAX_NODES)
+    }
+
+    /// Creates a new sink that records at most `max_nodes` nodes
+    ///
+    /// Allocations seen after the limit is reached are merged into their
+    /// owners, the owners of recorded nodes are always recorded so the graph
+    /// can exceed the limit by the number of owners still being measured
+    #[inline]
+    pub fn with_max_nodes(max_nodes: usize) -> Self {
+        Self {
+            max_nodes,
+            nodes: Vec::new(),
+            edges: BTreeSet::new(),
+            shared: BTreeMap::new(),
+            frames: Vec::new(),
+            omitted: 0,
+        }
+    }
+
+    /// Returns the number of nodes within the graph
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns `true` if the graph contains no nodes
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Returns the number of edges within the graph
+    #[inline]
+    pub fn edges(&self) -> usize {
+        self.edges.len()
+    }
+
+    /// Returns the number of nodes for shared allocations
+    #[inline]
+    pub fn shared_nodes(&self) -> usize {
+        self.shared.len()
+    }
+
+    /// Returns the number of allocations that were merged into their owners
+    /// because the graph was full
+    #[inline]
+    pub const fn omitted_allocations(&self) -> usize {
+        self.omitted
+    }
+
+    /// Exits the current type, turning it into a node if it owns an
+    /// allocation or it's a measured value and merging it into its owner
+    /// otherwise
+    fn exit_frame(&mut self, frame: Frame) {
+        let is_root = self.frames.is_empty();
+        let is_node = !frame.is_seen && (is_root || frame.allocations != 0);
+        let fits = is_root || !frame.children.is_empty() || self.nodes.len() < self.max_nodes;
+
+        if is_node && fits {
+            let node = self.nodes.len();
+            let shared = self
+                .frames
+                .last()
+                .filter(|parent| !parent.is_seen)
+                .and_then(|parent| parent.shared);
+
+            self.nodes.push(DotNode {
+                type_name: frame.type_name.unwrap_or_default(),
+                bytes: frame.bytes,
+                is_shared: shared.is_some(),
+            });
+            self.edges
+                .extend(frame.children.iter().map(|&child| (node, child)));
+
+            if let Some(addr) = shared {
+                self.shared.insert(addr, node);
+            }
+            if let Some(parent) = self.frames.last_mut() {
+                parent.children.push(node);
+            }
+        } else {
+            if is_node {
+                self.omitted += 1;
+            }
+            self.merge_into_parent(frame);
+        }
+    }
+
+    fn merge_into_parent(&mut self, frame: Frame) {
+        if let Some(parent) = self.frames.last_mut() {
+            parent.bytes = parent.bytes.saturating_add(frame.bytes);
+            parent.children.extend(frame.children);
+        }
+    }
+}
+
+impl Default for DotSink {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SizeSink for DotSink {
+    fn record(&mut self, size: TotalSize) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.bytes = frame.bytes.saturating_add(size.total_bytes());
+            frame.allocations = frame
+                .allocations
+                .saturating_add(size.distinct_allocations());
+        }
+    }
+
+    fn enter_type(&mut self, type_name: &'static str) {
+        let is_seen = self.frames.last().is_some_and(|parent| parent.is_seen);
+        self.frames.push(Frame {
+            type_name: Some(type_name),
+            is_seen,
+            ..Frame::default()
+        });
+    }
+
+    fn exit_type(&mut self) {
+        if let Some(frame) = self.frames.pop() {
+            self.exit_frame(frame);
+        }
+    }
+
+    fn enter_shared(&mut self, addr: usize) {
+        let is_seen = self.frames.last().is_some_and(|parent| parent.is_seen)
+            || self.shared.contains_key(&addr);
+        self.frames.push(Frame {
+            shared: Some(addr),
+            is_seen,
+            ..Frame::default()
+        });
+    }
+
+    fn exit_shared(&mut self) {
+        let Some(frame) = self.frames.pop() else {
+            return;
+        };
+
+        // Allocations that already have a node get another owner, anything
+        // recorded for them is part of that node
+        let existing = frame
+            .shared
+            .filter(|_| frame.is_seen)
+            .and_then(|addr| self.shared.get(&addr).copied());
+        match existing {
+            Some(node) => {
+                self.nodes[node].bytes = self.nodes[node].bytes.saturating_add(frame.bytes);
+                self.edges
+                    .extend(frame.children.iter().map(|&child| (node, child)));
+
+                if let Some(parent) = self.frames.last_mut() {
+                    parent.children.push(node);
+                }
+            }
+
+            None => self.merge_into_parent(frame),
+        }
+    }
+}
+
+impl Display for DotSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "digraph ownership {{")?;
+        writeln!(f, "    node [shape=box];")?;
+
+        for (idx, node) in self.nodes.iter().enumerate() {
+            write!(f, "    n{idx} [label=\"")?;
+            for char in node.type_name.chars() {
+                if matches!(char, '"' | '\\') {
+                    f.write_char('\\')?;
+                }
+                f.write_char(char)?;
+            }
+            write!(f, "\\n{}\"", HumanBytes::from(node.bytes))?;
+
+            // Shared allocations are outlined twice
+            if node.is_shared {
+                write!(f, ", peripheries=2")?;
+            }
+            writeln!(f, "];")?;
+        }
+
+        if self.omitted != 0 {
+            writeln!(
+                f,
+                "    omitted [shape=plaintext, label=\"{} more allocations\"];",
+                self.omitted,
+            )?;
+        }
+
+        for (owner, owned) in &self.edges {
+            writeln!(f, "    n{owner} -> n{owned};")?;
+        }
+
+        writeln!(f, "}}")
+    }
+}
+
+#[derive(Debug, Clone)]
+struct DotNode {
+    type_name: &'static str,
+    /// The bytes directly owned by the node
+    bytes: usize,
+    is_shared: bool,
+}
+
+/// A type or shared allocation that's currently being measured
+#[derive(Debug, Clone, Default)]
+struct Frame {
+    /// The name of the type, `None` for shared allocations
+    type_name: Option<&'static str>,
+    /// The address of the shared allocation
+    shared: Option<usize>,
+    /// Whether the frame is within a shared allocation that already has a
+    /// node, in which case no new nodes are created
+    is_seen: bool,
+    bytes: usize,
+    allocations: usize,
+    /// The nodes owned by the frame
+    children: Vec<usize>,
+}
@@ -0,0 +1,244 @@
+use crate::{Context, HumanBytes, SizeOf};
+use alloc::{format, string::ToString};
+use core::fmt::{self, Display};
+
+/// The number of buckets within a histogram, one for every power of two a
+/// `usize` can hold along with one for allocations of zero or one bytes
+pub(crate) const BUCKETS: usize = usize::BITS as usize + 1;
+
+/// A histogram of the sizes of all allocations a [`Context`] has seen, grouped
+/// into power-of-two buckets
+///
+/// Created by [`Context::allocation_histogram()`], allocations that were
+/// recorded without a size (see [`Context::add_distinct_allocation()`]) are
+/// only counted within [`AllocationHistogram::unknown_sizes()`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "SerializedHistogram", into = "SerializedHistogram")
+)]
+pub struct AllocationHistogram {
+    /// The number of allocations within each bucket
+    allocations: [usize; BUCKETS],
+    /// The total bytes of the allocations within each bucket
+    bytes: [usize; BUCKETS],
+    /// The number of allocations with an unknown size
+    unknown_sizes: usize,
+}
+
+impl AllocationHistogram {
+    /// Creates an empty histogram
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            allocations: [0; BUCKETS],
+            bytes: [0; BUCKETS],
+            unknown_sizes: 0,
+        }
+    }
+
+    /// Records `allocations` allocations of `size` bytes each
+    #[inline]
+    pub(crate) fn record(&mut self, allocations: usize, size: usize) {
+        let bucket = bucket_of(size);
+        self.allocations[bucket] = self.allocations[bucket].saturating_add(allocations);
+        self.bytes[bucket] = self.bytes[bucket].saturating_add(size.saturating_mul(allocations));
+    }
+
+    /// Records `allocations` allocations with an unknown size
+    #[inline]
+    pub(crate) fn record_unknown(&mut self, allocations: usize) {
+        self.unknown_sizes = self.unknown_sizes.saturating_add(allocations);
+    }
+
+    /// Adds all allocations recorded by `other` to the current histogram
+    pub(crate) fn merge(&mut self, other: &Self) {
+        for bucket in 0..BUCKETS {
+            self.allocations[bucket] =
+                self.allocations[bucket].saturating_add(other.allocations[bucket]);
+            self.bytes[bucket] = self.bytes[bucket].saturating_add(other.bytes[bucket]);
+        }
+        self.record_unknown(other.unknown_sizes);
+    }
+
+    /// Returns the number of allocations that were recorded without a size
+    #[inline]
+    pub const fn unknown_sizes(&self) -> usize {
+        self.unknown_sizes
+    }
+
+    /// Returns the total number of allocations within the histogram, including
+    /// ones with an unknown size
+    #[inline]
+    pub fn total_allocations(&self) -> usize {
+        self.allocations
+            .iter()
+            .fold(self.unknown_sizes, |total, &count| {
+                total.saturating_add(count)
+            })
+    }
+
+    /// Returns `true` if the histogram doesn't contain any allocations
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.total_allocations() == 0
+    }
+
+    /// Returns the bucket that allocations of `size` bytes fall into
+    #[inline]
+    pub fn bucket(&self, size: usize) -> HistogramBucket {
+        self.bucket_at(bucket_of(size))
+    }
+
+    /// Returns an iterator over every bucket that contains allocations,
+    /// ordered from the smallest sizes to the largest
+    #[inline]
+    pub fn buckets(&self) -> impl Iterator<Item = HistogramBucket> + '_ {
+        (0..BUCKETS)
+            .filter(|&bucket| self.allocations[bucket] != 0)
+            .map(|bucket| self.bucket_at(bucket))
+    }
+
+    fn bucket_at(&self, bucket: usize) -> HistogramBucket {
+        let min_size = match bucket {
+            0 => 0,
+            bucket => (1 << (bucket - 1)) + 1,
+        };
+        let max_size = match bucket {
+            bucket if bucket == BUCKETS - 1 => usize::MAX,
+            bucket => 1 << bucket,
+        };
+
+        HistogramBucket {
+            min_size,
+            max_size,
+            allocations: self.allocations[bucket],
+            total_bytes: self.bytes[bucket],
+        }
+    }
+}
+
+impl Default for AllocationHistogram {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display for AllocationHistogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:>12} {:>12} {:>12}", "size", "allocations", "total")?;
+
+        for bucket in self.buckets() {
+            writeln!(
+                f,
+                "{:>12} {:>12} {:>12}",
+                format!("<= {}", HumanBytes::from(bucket.max_size)),
+                bucket.allocations,
+                HumanBytes::from(bucket.total_bytes).to_string(),
+            )?;
+        }
+
+        if self.unknown_sizes != 0 {
+            writeln!(f, "{:>12} {:>12}", "unknown", self.unknown_sizes)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl SizeOf for AllocationHistogram {
+    #[inline]
+    fn size_of_children(&self, _context: &mut Context) {}
+
+    const IS_CHILDLESS: bool = true;
+}
+
+/// A single bucket of an [`AllocationHistogram`], which holds all
+/// allocations with sizes between its minimum and maximum size (inclusive)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HistogramBucket {
+    min_size: usize,
+    max_size: usize,
+    allocations: usize,
+    total_bytes: usize,
+}
+
+impl HistogramBucket {
+    /// Returns the smallest allocation size within the bucket
+    #[inline]
+    pub const fn min_size(&self) -> usize {
+        self.min_size
+    }
+
+    /// Returns the largest allocation size within the bucket, always a power
+    /// of two except for the last bucket which holds everything larger than
+    /// the largest power of two
+    #[inline]
+    pub const fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// Returns the number of allocations within the bucket
+    #[inline]
+    pub const fn allocations(&self) -> usize {
+        self.allocations
+    }
+
+    /// Returns the total bytes of all allocations within the bucket
+    #[inline]
+    pub const fn total_bytes(&self) -> usize {
+        self.total_bytes
+    }
+}
+
+impl SizeOf for HistogramBucket {
+    #[inline]
+    fn size_of_children(&self, _context: &mut Context) {}
+
+    const IS_CHILDLESS: bool = true;
+}
+
+/// The serialized form of an [`AllocationHistogram`], which only holds its
+/// non-empty buckets
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SerializedHistogram {
+    buckets: alloc::vec::Vec<HistogramBucket>,
+    unknown_sizes: usize,
+}
+
+#[cfg(feature = "serde")]
+impl From<AllocationHistogram> for SerializedHistogram {
+    fn from(histogram: AllocationHistogram) -> Self {
+        Self {
+            buckets: histogram.buckets().collect(),
+            unknown_sizes: histogram.unknown_sizes,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<SerializedHistogram> for AllocationHistogram {
+    fn from(serialized: SerializedHistogram) -> Self {
+        let mut histogram = Self::new();
+        for bucket in serialized.buckets {
+            let idx = bucket_of(bucket.max_size);
+            histogram.allocations[idx] =
+                histogram.allocations[idx].saturating_add(bucket.allocations);
+            histogram.bytes[idx] = histogram.bytes[idx].saturating_add(bucket.total_bytes);
+        }
+        histogram.unknown_sizes = serialized.unknown_sizes;
+
+        histogram
+    }
+}
+
+/// Returns the bucket allocations of `size` bytes fall into, bucket `n` holds
+/// the sizes greater than `2ⁿ⁻¹` and at most `2ⁿ`
+#[inline]
+pub(crate) const fn bucket_of(size: usize) -> usize {
+    (usize::BITS - size.saturating_sub(1).leading_zeros()) as usize
+}
@@ -3,6 +3,11 @@ use core::fmt::{self, Display};
 
 /// Friendlier formatting for byte values
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 #[repr(transparent)]
 pub struct HumanBytes {
     /// The inner bytes
@@ -9,46 +9,70 @@ extern crate alloc;
 
 #[macro_use]
 mod macros;
+pub mod allocator;
 mod arch;
 mod collections;
 mod core_impls;
+mod diff;
+mod dot;
+mod histogram;
 mod human_bytes;
+mod locks;
+mod metrics;
+mod parallel;
+mod pointer_set;
 mod pointers;
+mod report;
+mod retained;
+mod sampling;
+mod sink;
 mod std_impls;
 mod support;
 mod tests;
+mod type_report;
 
+pub use allocator::AllocatorModel;
+pub use diff::{DiffEntry, DiffReport, SizeDiff};
+pub use dot::DotSink;
+pub use histogram::{AllocationHistogram, HistogramBucket};
 pub use human_bytes::HumanBytes;
+pub use locks::LockPolicy;
+pub use metrics::{component_sizes, ComponentReport, ComponentSize};
+#[cfg(feature = "rayon")]
+pub use parallel::ParSizeOf;
+pub use pointer_set::PointerSnapshot;
+pub use pointers::{SharedAttribution, WeakPolicy};
+pub use report::{ByteMetric, SizeNode, SizeReport, SizeReportIter};
+pub use retained::{retained_sizes, RetainedReport, RetainedSize};
+pub use sampling::SamplingReport;
+pub use sink::SizeSink;
 #[cfg(feature = "derive")]
 pub use size_of_derive::SizeOf;
+pub use type_report::{TypeReport, TypeSize};
 
-use alloc::{collections::BTreeSet, rc::Rc, sync::Arc};
+use alloc::{borrow::Cow, boxed::Box, collections::BTreeMap, sync::Arc, vec::Vec};
 use core::{
+    any::type_name,
     iter::Sum,
-    mem::{replace, size_of_val},
+    mem::{replace, size_of_val, take},
     ops::{Add, AddAssign, Sub, SubAssign},
 };
-
-// TODO: There's some things we could do with allocator-specific size queries
-// which would allow us to get the "real" size of everything we interact with.
-// Allocators often round up allocation sizes which means that the true size of
-// an allocation can differ from the "declared" size, like an allocator giving
-// back 4096 bytes if a `Box<[u8; 4000]>` was allocated. Ideally we'd report
-// that 4000 bytes were used, but 4096 bytes were allocated in total. This does
-// heavily complicate things since we'd need to add support for as many
-// different allocators as possible such as various malloc implementations,
-// VirtualAlloc(), mimalloc, jemalloc, etc.
+use pointer_set::PointerSet;
+use report::TreeBuilder;
+use sampling::SamplingStats;
+use sink::Sinks;
+use type_report::{SizeStats, TypeStats};
 
 /// Get the total size of all given values
 ///
 /// ```rust
 /// use core::mem::size_of;
-/// use size_of::SizeOf;
+/// use size_of::DynSizeOf;
 ///
 /// let vector: Vec<u8> = vec![1, 2, 3, 4];
 /// let array: [u8; 10] = [255; 10];
 ///
-/// let size = size_of::size_of_values([&vector as &dyn SizeOf, &array as &dyn SizeOf]);
+/// let size = size_of::size_of_values([&vector as &dyn DynSizeOf, &array as &dyn DynSizeOf]);
 /// assert_eq!(
 ///     size.total_bytes(),
 ///     size_of::<Vec<u8>>() + (size_of::<u8>() * 4) + size_of::<[u8; 10]>(),
@@ -57,12 +81,12 @@ use core::{
 #[inline]
 pub fn size_of_values<'a, I>(values: I) -> TotalSize
 where
-    I: IntoIterator<Item = &'a dyn SizeOf> + 'a,
+    I: IntoIterator<Item = &'a dyn DynSizeOf> + 'a,
 {
     let mut context = Context::new();
     values
         .into_iter()
-        .for_each(|value| value.size_of_with_context(&mut context));
+        .for_each(|value| value.dyn_size_of_with_context(&mut context));
     context.total_size()
 }
 
@@ -76,110 +100,1129 @@ pub trait SizeOf {
         context.total_size()
     }
 
-    /// Adds the size of the current value to the given [`Context`],
-    /// including both the size of the value itself and all of its children
+    /// Gets a hierarchical breakdown of the current value's size, with a
+    /// node for each field, variant and collection within it
+    ///
+    /// ```rust
+    /// use size_of::{Context, SizeOf};
+    ///
+    /// struct Service {
+    ///     name: String,
+    ///     cache: Vec<u64>,
+    /// }
+    ///
+    /// // `#[derive(SizeOf)]` names a node after every field in the same way
+    /// impl SizeOf for Service {
+    ///     fn size_of_children(&self, context: &mut Context) {
+    ///         context
+    ///             .named("name", |ctx| self.name.size_of_children(ctx))
+    ///             .named("cache", |ctx| self.cache.size_of_children(ctx));
+    ///     }
+    /// }
+    ///
+    /// let service = Service {
+    ///     name: String::from("service"),
+    ///     cache: vec![0; 1024],
+    /// };
+    ///
+    /// let report = service.size_of_tree();
+    /// let cache = report.root().child("cache").unwrap();
+    /// assert_eq!(cache.size().total_bytes(), 1024 * 8);
+    /// ```
+    #[inline]
+    fn size_of_tree(&self) -> SizeReport {
+        let mut context = Context::new();
+        context.record_tree();
+        self.size_of_with_context(&mut context);
+        context.size_report(type_name::<Self>())
+    }
+
+    /// Adds the size of the current value to the given [`Context`],
+    /// including both the size of the value itself and all of its children
+    #[inline]
+    fn size_of_with_context(&self, context: &mut Context) {
+        context.attribute_to::<Self, _>(|ctx| {
+            ctx.add_inline(size_of_val(self));
+            self.size_of_children(ctx);
+        });
+    }
+
+    /// Gets the size of all "children" owned by this value, not including the
+    /// size of the value itself.
+    ///
+    /// This should add all heap allocations owned by the current value to the
+    /// given context
+    fn size_of_children(&self, context: &mut Context);
+
+    /// `true` if values of this type never have any children, meaning that
+    /// [`SizeOf::size_of_children()`] never adds anything to the context
+    ///
+    /// Collections skip visiting their elements when the element type is
+    /// childless, so measuring a `Vec<u64>` doesn't depend on its length. This
+    /// is implemented by `derive(SizeOf)` and should only be overridden when
+    /// it's known to be true
+    const IS_CHILDLESS: bool = false;
+}
+
+/// An object safe version of [`SizeOf`] for measuring values of different types
+/// together, implemented for every type that implements [`SizeOf`]
+///
+/// ```rust
+/// use core::mem::size_of;
+/// use size_of::{DynSizeOf, SizeOf};
+///
+/// let values: Vec<Box<dyn DynSizeOf>> = vec![Box::new(String::from("value")), Box::new(0u64)];
+/// assert_eq!(
+///     values.size_of().heap_bytes(),
+///     2 * size_of::<Box<dyn DynSizeOf>>() + size_of::<String>() + 5 + size_of::<u64>(),
+/// );
+/// ```
+pub trait DynSizeOf {
+    /// Adds the size of the current value to the given [`Context`], see
+    /// [`SizeOf::size_of_with_context()`]
+    fn dyn_size_of_with_context(&self, context: &mut Context);
+
+    /// Adds the size of the current value's children to the given [`Context`],
+    /// see [`SizeOf::size_of_children()`]
+    fn dyn_size_of_children(&self, context: &mut Context);
+}
+
+impl<T> DynSizeOf for T
+where
+    T: SizeOf + ?Sized,
+{
+    #[inline]
+    fn dyn_size_of_with_context(&self, context: &mut Context) {
+        self.size_of_with_context(context);
+    }
+
+    #[inline]
+    fn dyn_size_of_children(&self, context: &mut Context) {
+        self.size_of_children(context);
+    }
+}
+
+impl SizeOf for dyn DynSizeOf + '_ {
+    #[inline]
+    fn size_of_with_context(&self, context: &mut Context) {
+        self.dyn_size_of_with_context(context);
+    }
+
+    #[inline]
+    fn size_of_children(&self, context: &mut Context) {
+        self.dyn_size_of_children(context);
+    }
+}
+
+/// The context of a size query, used to keep track of shared pointers and the
+/// aggregated totals of seen data
+#[derive(Debug, Clone, Default)]
+pub struct Context {
+    /// The totals of everything the context has seen
+    totals: TotalSize,
+    /// Whether all added bytes should be marked as shared
+    is_shared: bool,
+    /// Whether all added bytes are behind a followed reference
+    is_borrowed: bool,
+    /// Whether all added bytes are estimated
+    is_estimated: bool,
+    /// Whether any of the counts overflowed
+    overflowed: bool,
+    /// Whether references (`&T` and `&mut T`) should be followed
+    follow_references: bool,
+    /// How weak pointers are recorded
+    weak_policy: WeakPolicy,
+    /// How the contents of locks are measured
+    lock_policy: LockPolicy,
+    /// How shared allocations are attributed to their owners
+    shared_attribution: SharedAttribution,
+    /// The full size of each shared allocation seen, only recorded when
+    /// shared allocations are attributed proportionally
+    shared_sizes: BTreeMap<usize, TotalSize>,
+    /// Keeps track of all owned pointers (`Rc` and `Arc`) we've seen
+    pointers: PointerSet,
+    /// Keeps track of all references (`&T` and `&mut T`) we've followed, kept
+    /// apart from `pointers` so that borrowing a shared allocation never stops
+    /// it from being counted by its owners
+    references: PointerSet,
+    /// Keeps track of the shared pointers seen behind followed references,
+    /// kept apart from `pointers` for the same reason as `references`
+    borrowed_pointers: PointerSet,
+    /// The pointers seen by every thread, only present while visiting elements
+    /// in parallel and used instead of `pointers`
+    #[cfg(feature = "rayon")]
+    parallel_pointers: Option<Arc<parallel::ParallelPointers>>,
+    /// The tree of named nodes, only recorded when requested
+    tree: Option<TreeBuilder>,
+    /// The memory used by each type, only recorded when requested
+    types: Option<TypeStats>,
+    /// The memory directly used by each shared allocation, only recorded when
+    /// requested
+    shared_allocations: Option<SizeStats<usize>>,
+    /// The sizes of all allocations, only recorded when requested
+    histogram: Option<AllocationHistogram>,
+    /// The model used to figure out how much allocators round up allocations
+    allocator: Option<Arc<dyn AllocatorModel>>,
+    /// The maximum number of elements measured within each collection, all
+    /// elements are measured when this is `None`
+    sample_size: Option<usize>,
+    /// The sampling done by the context
+    sampling: SamplingStats,
+    /// The maximum depth of owned pointers and collections traversed, there's
+    /// no limit when this is `None`
+    max_depth: Option<usize>,
+    /// The total bytes after which the context stops traversing, there's no
+    /// limit when this is `None`
+    byte_budget: Option<usize>,
+    /// The number of owned pointers and collections currently being traversed
+    depth: usize,
+    /// The sinks everything added is reported to
+    sinks: Sinks,
+}
+
+impl Context {
+    /// Creates a new, empty context
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new, empty context which can hold at least `capacity`
+    /// distinct pointers without reallocating, see
+    /// [`Context::reserve_pointers()`]
+    #[inline]
+    pub fn with_pointer_capacity(capacity: usize) -> Self {
+        Self {
+            pointers: PointerSet::with_capacity(capacity),
+            ..Self::default()
+        }
+    }
+
+    /// Reserves space for at least `additional` more distinct pointers, useful
+    /// when measuring values with a large number of `Rc` or `Arc`s
+    #[inline]
+    pub fn reserve_pointers(&mut self, additional: usize) -> &mut Self {
+        self.pointers.reserve(additional);
+        self
+    }
+
+    /// Returns the number of distinct pointers the context has seen
+    #[inline]
+    pub fn seen_pointers(&self) -> usize {
+        self.pointers.len()
+    }
+
+    /// Returns a snapshot of the pointers the context has seen, which can be
+    /// restored later with [`Context::restore_pointers()`]
+    #[inline]
+    pub fn pointer_snapshot(&self) -> PointerSnapshot {
+        PointerSnapshot::new(self.pointers.clone())
+    }
+
+    /// Replaces the pointers the context has seen with the ones within
+    /// `snapshot`, reusing the context's allocated pointer set if it can
+    ///
+    /// This makes the context forget any pointers it's seen since the snapshot
+    /// was taken while keeping its counts, so shared values seen since then
+    /// will be counted again
+    #[inline]
+    pub fn restore_pointers(&mut self, snapshot: &PointerSnapshot) -> &mut Self {
+        self.pointers.clone_from(snapshot.pointers());
+        self
+    }
+
+    /// Resets the context to the state it was created in while keeping its
+    /// allocated memory and settings, so one context can be reused for
+    /// repeated measurements
+    ///
+    /// Everything the context has recorded is cleared, including its counts,
+    /// the pointers it has seen and its recorded trees and types. Its settings
+    /// (policies, the sample size and its allocator model) and its sinks are
+    /// kept as they are
+    ///
+    /// ```rust
+    /// use size_of::{Context, SizeOf};
+    ///
+    /// let mut context = Context::new();
+    /// for value in [vec![0u8; 16], vec![0u8; 32]] {
+    ///     context.reset();
+    ///     value.size_of_with_context(&mut context);
+    ///     assert_eq!(context.total_size(), value.size_of());
+    /// }
+    /// ```
+    pub fn reset(&mut self) -> &mut Self {
+        self.totals = TotalSize::zero();
+        self.overflowed = false;
+        self.pointers.clear();
+        self.references.clear();
+        self.borrowed_pointers.clear();
+        self.shared_sizes.clear();
+
+        if let Some(tree) = &mut self.tree {
+            tree.clear();
+        }
+        if let Some(types) = &mut self.types {
+            types.clear();
+        }
+        if let Some(stats) = &mut self.shared_allocations {
+            stats.clear();
+        }
+        if let Some(histogram) = &mut self.histogram {
+            *histogram = AllocationHistogram::new();
+        }
+        self.sampling = SamplingStats::default();
+
+        self
+    }
+
+    /// Returns `true` if the current context is shared
+    #[inline]
+    pub const fn is_shared(&self) -> bool {
+        self.is_shared
+    }
+
+    /// Run the given closure and mark all added allocations as shared
+    #[inline]
+    pub fn shared<F>(&mut self, with_shared: F) -> &mut Self
+    where
+        F: FnOnce(&mut Self),
+    {
+        let prev = replace(&mut self.is_shared, true);
+        with_shared(self);
+        self.is_shared = prev;
+        self
+    }
+
+    /// Returns `true` if the current context is borrowed, see
+    /// [`Context::borrowed()`]
+    #[inline]
+    pub const fn is_borrowed(&self) -> bool {
+        self.is_borrowed
+    }
+
+    /// Run the given closure and mark all added bytes as borrowed
+    ///
+    /// Borrowed bytes are only added to the borrowed bytes and no allocations
+    /// are recorded for them, so they don't inflate the owned totals
+    #[inline]
+    pub fn borrowed<F>(&mut self, with_borrowed: F) -> &mut Self
+    where
+        F: FnOnce(&mut Self),
+    {
+        let prev = replace(&mut self.is_borrowed, true);
+        with_borrowed(self);
+        self.is_borrowed = prev;
+        self
+    }
+
+    /// Returns `true` if the current context is estimated, see
+    /// [`Context::estimated()`]
+    #[inline]
+    pub const fn is_estimated(&self) -> bool {
+        self.is_estimated
+    }
+
+    /// Run the given closure and mark all added bytes as estimated
+    ///
+    /// Implementations that model their memory usage instead of reading it
+    /// from their value (like the nodes of a `BTreeMap`) should add their
+    /// bytes within this, see [`TotalSize::estimated_bytes()`]
+    #[inline]
+    pub fn estimated<F>(&mut self, with_estimated: F) -> &mut Self
+    where
+        F: FnOnce(&mut Self),
+    {
+        let prev = replace(&mut self.is_estimated, true);
+        with_estimated(self);
+        self.is_estimated = prev;
+        self
+    }
+
+    /// Returns `true` if any of the context's counts overflowed
+    ///
+    /// Counts that would overflow saturate at `usize::MAX` instead, which can
+    /// happen on 32-bit targets or when measuring values with corrupted
+    /// lengths or capacities
+    #[inline]
+    pub const fn has_overflowed(&self) -> bool {
+        self.overflowed
+    }
+
+    /// Sets whether the context follows references (`&T` and `&mut T`)
+    ///
+    /// By default only owned memory is traversed, when following references
+    /// the data behind each distinct reference is measured once and recorded
+    /// as borrowed bytes, see [`TotalSize::borrowed_bytes()`]
+    #[inline]
+    pub fn set_follow_references(&mut self, follow_references: bool) -> &mut Self {
+        self.follow_references = follow_references;
+        self
+    }
+
+    /// Returns `true` if the context follows references
+    #[inline]
+    pub const fn follows_references(&self) -> bool {
+        self.follow_references
+    }
+
+    /// Sets how the context records weak pointers (`rc::Weak` and
+    /// `sync::Weak`), see [`WeakPolicy`]
+    #[inline]
+    pub fn set_weak_policy(&mut self, weak_policy: WeakPolicy) -> &mut Self {
+        self.weak_policy = weak_policy;
+        self
+    }
+
+    /// Returns the context's weak pointer policy
+    #[inline]
+    pub const fn weak_policy(&self) -> WeakPolicy {
+        self.weak_policy
+    }
+
+    /// Sets how the context measures the contents of locks (`Mutex` and
+    /// `RwLock`), see [`LockPolicy`]
+    #[inline]
+    pub fn set_lock_policy(&mut self, lock_policy: LockPolicy) -> &mut Self {
+        self.lock_policy = lock_policy;
+        self
+    }
+
+    /// Returns the context's lock policy
+    #[inline]
+    pub const fn lock_policy(&self) -> LockPolicy {
+        self.lock_policy
+    }
+
+    /// Sets how the context attributes shared allocations (`Rc` and `Arc`) to
+    /// their owners, see [`SharedAttribution`]
+    #[inline]
+    pub fn set_shared_attribution(&mut self, shared_attribution: SharedAttribution) -> &mut Self {
+        self.shared_attribution = shared_attribution;
+        self
+    }
+
+    /// Returns how the context attributes shared allocations
+    #[inline]
+    pub const fn shared_attribution(&self) -> SharedAttribution {
+        self.shared_attribution
+    }
+
+    /// Makes the context record a tree of all named nodes it sees, see
+    /// [`Context::named()`] and [`Context::size_report()`]
+    #[inline]
+    pub fn record_tree(&mut self) -> &mut Self {
+        if self.tree.is_none() {
+            self.tree = Some(TreeBuilder::new());
+        }
+
+        self
+    }
+
+    /// Returns `true` if the context is recording a tree of named nodes
+    #[inline]
+    pub const fn is_recording_tree(&self) -> bool {
+        self.tree.is_some()
+    }
+
+    /// Run the given closure and record everything it adds under a node
+    /// named `name`
+    ///
+    /// Nodes with the same name under the same parent are merged together,
+    /// so measuring every element of a collection under the same name produces
+    /// a single node. Does nothing but run the closure if the context isn't
+    /// recording a tree
+    #[inline]
+    pub fn named<F>(&mut self, name: &'static str, with_named: F) -> &mut Self
+    where
+        F: FnOnce(&mut Self),
+    {
+        // Only enter the node when something records it, so the closure is
+        // only called from one place
+        let recording = self.tree.is_some() || !self.sinks.is_empty();
+        if recording {
+            self.enter_node(name);
+        }
+
+        with_named(self);
+
+        if recording {
+            self.exit_node();
+        }
+
+        self
+    }
+
+    /// Enters a node named `name` within the tree and sinks, see
+    /// [`Context::named()`]
+    fn enter_node(&mut self, name: &'static str) {
+        let start = self.total_size();
+        if let Some(tree) = &mut self.tree {
+            tree.enter(name, start);
+        }
+        self.sinks.for_each(|sink| sink.enter_node(name));
+    }
+
+    /// Exits the node most recently entered by [`Context::enter_node()`]
+    fn exit_node(&mut self) {
+        let end = self.total_size();
+        if let Some(tree) = &mut self.tree {
+            tree.exit(end);
+        }
+        self.sinks.for_each(|sink| sink.exit_node());
+    }
+
+    /// Returns the tree of named nodes recorded by the current context with
+    /// a root node named `name`
+    ///
+    /// The report will only contain the root node unless the context was
+    /// recording a tree, see [`Context::record_tree()`]
+    #[inline]
+    pub fn size_report<N>(&self, name: N) -> SizeReport
+    where
+        N: Into<Cow<'static, str>>,
+    {
+        match &self.tree {
+            Some(tree) => tree.build(name.into(), self.total_size()),
+            None => SizeReport::new(SizeNode::new(name, self.total_size(), Vec::new())),
+        }
+    }
+
+    /// Makes the context record the memory used by each type it sees, see
+    /// [`Context::attribute_to()`] and [`Context::type_report()`]
+    #[inline]
+    pub fn record_types(&mut self) -> &mut Self {
+        if self.types.is_none() {
+            self.types = Some(TypeStats::default());
+        }
+
+        self
+    }
+
+    /// Returns `true` if the context is recording the memory used by each type
+    #[inline]
+    pub const fn is_recording_types(&self) -> bool {
+        self.types.is_some()
+    }
+
+    /// Run the given closure and attribute everything it adds to the type `T`
+    ///
+    /// Anything attributed to another type by a nested call is excluded from
+    /// `T`, so a `Vec<String>` is only attributed its own buffer while the
+    /// buffers of its strings are attributed to `String`. Does nothing but run
+    /// the closure if the context isn't recording types
+    #[inline]
+    pub fn attribute_to<T, F>(&mut self, with_type: F) -> &mut Self
+    where
+        T: ?Sized,
+        F: FnOnce(&mut Self),
+    {
+        // Only enter the type when something records it, see `Context::named()`
+        let recording = self.types.is_some() || !self.sinks.is_empty();
+        let entered = recording && self.enter_type(type_name::<T>());
+
+        with_type(self);
+
+        if recording {
+            self.exit_type(entered);
+        }
+
+        self
+    }
+
+    /// Enters the type named `type_name` within the type stats and sinks,
+    /// returning `true` if the type stats entered it, see
+    /// [`Context::attribute_to()`]
+    fn enter_type(&mut self, type_name: &'static str) -> bool {
+        let start = self.total_size();
+        let entered = match &mut self.types {
+            Some(types) => types.enter(type_name, start),
+            None => false,
+        };
+        self.sinks.for_each(|sink| sink.enter_type(type_name));
+
+        entered
+    }
+
+    /// Exits the type most recently entered by [`Context::enter_type()`]
+    fn exit_type(&mut self, entered: bool) {
+        if entered {
+            let end = self.total_size();
+            if let Some(types) = &mut self.types {
+                types.exit(end);
+            }
+        }
+        self.sinks.for_each(|sink| sink.exit_type());
+    }
+
+    /// Returns the memory used by each type recorded by the current context
+    ///
+    /// The report will be empty unless the context was recording types, see
+    /// [`Context::record_types()`]
+    #[inline]
+    pub fn type_report(&self) -> TypeReport {
+        self.types
+            .as_ref()
+            .map(TypeStats::report)
+            .unwrap_or_default()
+    }
+
+    /// Makes the context record a histogram of the sizes of all allocations it
+    /// sees, see [`Context::allocation_histogram()`]
+    #[inline]
+    pub fn record_allocation_histogram(&mut self) -> &mut Self {
+        if self.histogram.is_none() {
+            self.histogram = Some(AllocationHistogram::new());
+        }
+
+        self
+    }
+
+    /// Returns `true` if the context is recording a histogram of allocation
+    /// sizes
+    #[inline]
+    pub const fn is_recording_allocation_histogram(&self) -> bool {
+        self.histogram.is_some()
+    }
+
+    /// Returns the histogram of the sizes of all allocations recorded by the
+    /// current context
+    ///
+    /// The histogram will be empty unless the context was recording it, see
+    /// [`Context::record_allocation_histogram()`]. Allocations of shared values
+    /// are only recorded once no matter how they're attributed and the
+    /// extrapolated allocations of sampled collections aren't recorded
+    #[inline]
+    pub fn allocation_histogram(&self) -> AllocationHistogram {
+        self.histogram.clone().unwrap_or_default()
+    }
+
+    /// Sets the allocator model the context uses to estimate how many bytes
+    /// allocations really take up, see [`Context::add_allocation()`]
+    #[inline]
+    pub fn set_allocator_model<A>(&mut self, allocator: A) -> &mut Self
+    where
+        A: AllocatorModel + 'static,
+    {
+        self.allocator = Some(Arc::new(allocator));
+        self
+    }
+
+    /// Returns the context's allocator model (if it has one)
+    #[inline]
+    pub fn allocator_model(&self) -> Option<&dyn AllocatorModel> {
+        self.allocator.as_deref()
+    }
+
+    /// Adds a sink that everything the context records is reported to, see
+    /// [`SizeSink`]
+    ///
+    /// Sinks aren't cloned along with the context and don't see the insides of
+    /// shared allocations that are attributed proportionally, only the share
+    /// charged to each owner
+    ///
+    /// ```rust
+    /// use size_of::{Context, SizeOf, SizeSink, TotalSize};
+    ///
+    /// /// Counts the number of allocations of each size
+    /// #[derive(Debug, Default)]
+    /// struct Allocations(Vec<usize>);
+    ///
+    /// impl SizeSink for Allocations {
+    ///     fn record(&mut self, size: TotalSize) {
+    ///         if size.distinct_allocations() != 0 {
+    ///             self.0.push(size.distinct_allocations());
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let mut context = Context::new();
+    /// context.add_sink(Allocations::default());
+    /// vec![Box::new(1), Box::new(2)].size_of_with_context(&mut context);
+    ///
+    /// let allocations = context.remove_sink::<Allocations>().unwrap();
+    /// assert_eq!(allocations.0, [1, 1, 1]);
+    /// ```
+    #[inline]
+    pub fn add_sink<S>(&mut self, sink: S) -> &mut Self
+    where
+        S: SizeSink,
+    {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    /// Returns the first of the context's sinks with the type `S`
+    #[inline]
+    pub fn sink<S>(&self) -> Option<&S>
+    where
+        S: SizeSink,
+    {
+        self.sinks.get()
+    }
+
+    /// Returns the first of the context's sinks with the type `S`
+    #[inline]
+    pub fn sink_mut<S>(&mut self) -> Option<&mut S>
+    where
+        S: SizeSink,
+    {
+        self.sinks.get_mut()
+    }
+
+    /// Removes the first of the context's sinks with the type `S` and
+    /// returns it
+    #[inline]
+    pub fn remove_sink<S>(&mut self) -> Option<S>
+    where
+        S: SizeSink,
+    {
+        self.sinks.remove()
+    }
+
+    /// Makes the context measure the children of at most `sample_size` elements
+    /// of each collection and extrapolate the children of the rest, `None`
+    /// measures every element (the default)
+    ///
+    /// Elements are sampled uniformly at random with a fixed seed, so measuring
+    /// the same value twice gives the same estimate. A sample size of zero is
+    /// treated as a sample size of one, see [`Context::sampling_report()`] for
+    /// how much of the total size was estimated. Within size and type reports
+    /// the extrapolated bytes are attributed to the collection itself
+    #[inline]
+    pub fn set_sample_size(&mut self, sample_size: Option<usize>) -> &mut Self {
+        self.sample_size = sample_size.map(|size| size.max(1));
+        self
+    }
+
+    /// Returns the maximum number of elements measured within each collection
+    #[inline]
+    pub const fn sample_size(&self) -> Option<usize> {
+        self.sample_size
+    }
+
+    /// Returns a report of how much of the context's total size was
+    /// extrapolated from samples, see [`Context::set_sample_size()`]
+    #[inline]
+    pub const fn sampling_report(&self) -> SamplingReport {
+        self.sampling.report(self.totals.total_bytes)
+    }
+
+    /// Makes the context stop traversing owned pointers and collections nested
+    /// more than `max_depth` levels deep, `None` traverses everything (the
+    /// default)
+    ///
+    /// The bytes of the skipped pointers and collections themselves are still
+    /// added, only their children are skipped and recorded within
+    /// [`TotalSize::truncated_values()`]. Arrays and slices are stored inline
+    /// and don't count as a level of their own
+    #[inline]
+    pub fn set_max_depth(&mut self, max_depth: Option<usize>) -> &mut Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Returns the maximum depth of owned pointers and collections traversed
+    #[inline]
+    pub const fn max_depth(&self) -> Option<usize> {
+        self.max_depth
+    }
+
+    /// Makes the context stop traversing once its total bytes reach
+    /// `byte_budget`, `None` traverses everything (the default)
+    ///
+    /// This gives a cheap answer to whether a value is larger than
+    /// `byte_budget`, the total bytes can go over the budget by the size of
+    /// the last collection or allocation added. Skipped values are recorded
+    /// within [`TotalSize::truncated_values()`]
+    #[inline]
+    pub fn set_byte_budget(&mut self, byte_budget: Option<usize>) -> &mut Self {
+        self.byte_budget = byte_budget;
+        self
+    }
+
+    /// Returns the total bytes after which the context stops traversing
+    #[inline]
+    pub const fn byte_budget(&self) -> Option<usize> {
+        self.byte_budget
+    }
+
+    /// Returns the number of owned pointers and collections currently being
+    /// traversed, see [`Context::descend()`]
+    #[inline]
+    pub const fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns `true` if any values were skipped because the context reached
+    /// its maximum depth or exhausted its byte budget
+    #[inline]
+    pub const fn is_truncated(&self) -> bool {
+        self.totals.is_truncated()
+    }
+
+    /// Traverses the children of an owned pointer or collection one level
+    /// deeper with `with_children`
+    ///
+    /// When the context has reached its maximum depth or exhausted its byte
+    /// budget `with_children` isn't called and the value is recorded as
+    /// truncated instead, see [`Context::set_max_depth()`] and
+    /// [`Context::set_byte_budget()`]
+    #[inline]
+    pub fn descend<F>(&mut self, with_children: F) -> &mut Self
+    where
+        F: FnOnce(&mut Self),
+    {
+        if self
+            .max_depth
+            .is_some_and(|max_depth| self.depth >= max_depth)
+            || self.is_over_budget()
+        {
+            return self.add_truncated(1);
+        }
+
+        self.depth += 1;
+        with_children(self);
+        self.depth -= 1;
+
+        self
+    }
+
+    /// Returns `true` if the context's total bytes have reached its byte
+    /// budget
+    #[inline]
+    fn is_over_budget(&self) -> bool {
+        self.byte_budget
+            .is_some_and(|budget| self.totals.total_bytes >= budget)
+    }
+
+    /// Records `values` values whose children were skipped
+    #[inline]
+    fn add_truncated(&mut self, values: usize) -> &mut Self {
+        self.record(TotalSize::zero().with_truncated_values(values))
+    }
+
+    /// Visits the given elements of a collection with `visit`, only a sample of
+    /// them are visited if the context has a sample size and the children of
+    /// the rest are extrapolated from the sampled elements
+    ///
+    /// The elements are visited one level deeper, see [`Context::descend()`]
+    ///
+    /// ```rust
+    /// use core::mem::size_of;
+    /// use size_of::{Context, SizeOf};
+    ///
+    /// struct Strings(Vec<String>);
+    ///
+    /// impl SizeOf for Strings {
+    ///     fn size_of_children(&self, context: &mut Context) {
+    ///         context.add_vectorlike_allocation(self.0.len(), self.0.capacity(), size_of::<String>());
+    ///         context.visit_elements(&self.0, |ctx, string| string.size_of_children(ctx));
+    ///     }
+    /// }
+    /// ```
+    pub fn visit_elements<I, F>(&mut self, elements: I, visit: F) -> &mut Self
+    where
+        I: IntoIterator,
+        I::IntoIter: ExactSizeIterator,
+        F: FnMut(&mut Self, I::Item),
+    {
+        let elements = elements.into_iter();
+        let total = elements.len();
+
+        self.descend(|ctx| ctx.visit_descended(elements, total, visit))
+    }
+
+    /// Visits the given elements of an inline collection like an array or a
+    /// slice without descending, since they don't own an allocation of their
+    /// own, see [`Context::visit_elements()`]
+    pub(crate) fn visit_inline_elements<I, F>(&mut self, elements: I, visit: F) -> &mut Self
+    where
+        I: IntoIterator,
+        I::IntoIter: ExactSizeIterator,
+        F: FnMut(&mut Self, I::Item),
+    {
+        let elements = elements.into_iter();
+        let total = elements.len();
+
+        self.visit_descended(elements, total, visit);
+        self
+    }
+
+    /// Visits `total` elements of a collection that's already been descended
+    /// into, see [`Context::visit_elements()`]
+    fn visit_descended<I, F>(&mut self, elements: I, total: usize, mut visit: F)
+    where
+        I: Iterator,
+        F: FnMut(&mut Self, I::Item),
+    {
+        match self.sample_size {
+            Some(sample_size) if total > sample_size => {
+                let (mut sum, mut sum_squares, mut selected) = (TotalSize::zero(), 0.0, 0);
+                let mut visited = total;
+                for (idx, element) in elements.enumerate() {
+                    // Select each element with a probability of the number of elements
+                    // we still need over the number of elements left, which selects
+                    // exactly `sample_size` elements
+                    if selected == sample_size {
+                        break;
+                    } else if !self.sampling.select(total - idx, sample_size - selected) {
+                        continue;
+                    }
+
+                    // Skip the rest of the elements once the budget runs out, only
+                    // the elements before this one are extrapolated
+                    if self.is_over_budget() {
+                        self.add_truncated(total - idx);
+                        visited = idx;
+                        break;
+                    }
+                    selected += 1;
+
+                    let start = self.total_size();
+                    visit(self, element);
+
+                    let delta = self.total_size() - start;
+                    sum += delta;
+                    sum_squares += delta.total_bytes as f64 * delta.total_bytes as f64;
+                }
+
+                if selected == 0 {
+                    return;
+                }
+
+                // Extrapolate the children of the elements that weren't sampled,
+                // all of which are estimated
+                let estimated = sum.scale(visited - selected, selected);
+                let estimated = estimated.with_estimated_bytes(estimated.total_bytes);
+                self.record(estimated);
+
+                self.sampling.record(
+                    visited,
+                    selected,
+                    sum.total_bytes as f64,
+                    sum_squares,
+                    estimated.total_bytes,
+                );
+            }
+
+            _ if self.byte_budget.is_some() => {
+                for (idx, element) in elements.enumerate() {
+                    // Skip the rest of the elements once the budget runs out
+                    if self.is_over_budget() {
+                        self.add_truncated(total - idx);
+                        break;
+                    }
+
+                    visit(self, element);
+                }
+            }
+
+            _ => elements.for_each(|element| visit(self, element)),
+        }
+    }
+
+    /// Adds one distinct allocation to the current context
+    ///
+    /// Prefer [`Context::add_allocation()`] when the size of the allocation is
+    /// known
+    #[inline]
+    pub fn add_distinct_allocation(&mut self) -> &mut Self {
+        self.add_distinct_allocations(1)
+    }
+
+    /// Adds one distinct allocation of `size` bytes to the current context
+    ///
+    /// This doesn't add `size` to the total bytes, only the allocation itself
+    /// is recorded. If the context has an allocator model the bytes the
+    /// allocator rounds `size` up by are added to the overhead bytes
+    ///
+    /// Collections should prefer [`Context::add_vectorlike_allocation()`] and
+    /// [`Context::add_arraylike_allocation()`], which add their bytes and
+    /// allocation at once
+    #[inline]
+    pub fn add_allocation(&mut self, size: usize) -> &mut Self {
+        self.add_allocations(1, size)
+    }
+
+    /// Adds `allocations` distinct allocations of `size` bytes each to the
+    /// current context, see [`Context::add_allocation()`]
+    #[inline]
+    pub fn add_allocations(&mut self, allocations: usize, size: usize) -> &mut Self {
+        // Only the allocation count changes when nothing else is recorded
+        if !self.is_borrowed
+            && self.histogram.is_none()
+            && self.allocator.is_none()
+            && self.sinks.is_empty()
+        {
+            add_count(
+                &mut self.totals.distinct_allocations,
+                allocations,
+                &mut self.overflowed,
+            );
+            return self;
+        }
+
+        self.record_allocations(allocations, size)
+    }
+
+    /// Adds `allocations` distinct allocations of `size` bytes each along
+    /// with their overhead and histogram buckets, kept out of line so
+    /// [`Context::add_allocations()`] stays small enough to inline
+    fn record_allocations(&mut self, allocations: usize, size: usize) -> &mut Self {
+        if self.is_borrowed {
+            return self;
+        }
+
+        if let Some(histogram) = &mut self.histogram {
+            histogram.record(allocations, size);
+        }
+
+        let overhead = match &self.allocator {
+            Some(allocator) if size != 0 => allocator.usable_size(size).saturating_sub(size),
+            _ => 0,
+        };
+        let overhead = self.saturate(overhead.checked_mul(allocations));
+
+        self.record(TotalSize::new(0, 0, 0, allocations).with_overhead_bytes(overhead))
+    }
+
+    /// Adds `allocations` distinct allocations to the current context
     #[inline]
-    fn size_of_with_context(&self, context: &mut Context) {
-        context.add(size_of_val(self));
-        self.size_of_children(context);
+    pub fn add_distinct_allocations(&mut self, allocations: usize) -> &mut Self {
+        if !self.is_borrowed {
+            if let Some(histogram) = &mut self.histogram {
+                histogram.record_unknown(allocations);
+            }
+            self.record(TotalSize::new(0, 0, 0, allocations));
+        }
+
+        self
     }
 
-    /// Gets the size of all "children" owned by this value, not including the
-    /// size of the value itself.
+    /// Adds the total, excess and inline bytes of `bytes`, this is where all
+    /// added bytes end up
     ///
-    /// This should add all heap allocations owned by the current value to the
-    /// given context
-    fn size_of_children(&self, context: &mut Context);
-}
-
-/// The context of a size query, used to keep track of shared pointers and the
-/// aggregated totals of seen data
-#[derive(Debug, Clone, Default)]
-pub struct Context {
-    /// The total bytes used
-    total_bytes: usize,
-    /// The total excess bytes, e.g. the excess capacity allocated by a `Vec`
-    excess_bytes: usize,
-    /// The total bytes shared between `Rc` and `Arc`-type collections
-    shared_bytes: usize,
-    /// The total number of distinct allocations made
-    distinct_allocations: usize,
-    /// Whether all added bytes should be marked as shared
-    is_shared: bool,
-    /// Keeps track of all pointers (`&T`, `Rc` and `Arc`) we've seen
-    pointers: BTreeSet<usize>,
-}
-
-impl Context {
-    /// Creates a new, empty context
+    /// - Adds the total bytes to the borrowed bytes and nothing else if the
+    ///   context is currently borrowed
+    /// - Adds the total bytes to the shared bytes if the context is currently
+    ///   shared
+    /// - Adds the total bytes to the estimated bytes if the context is
+    ///   currently estimated
     #[inline]
-    pub fn new() -> Self {
-        Self::default()
+    fn add_bytes(&mut self, bytes: TotalSize) -> &mut Self {
+        // Bytes that are owned, unshared and exact only change the counts
+        // they add to when nothing else is recorded
+        if !(self.is_borrowed || self.is_shared || self.is_estimated) && self.sinks.is_empty() {
+            let overflowed = &mut self.overflowed;
+            add_count(&mut self.totals.total_bytes, bytes.total_bytes, overflowed);
+            add_count(
+                &mut self.totals.excess_bytes,
+                bytes.excess_bytes,
+                overflowed,
+            );
+            add_count(
+                &mut self.totals.inline_bytes,
+                bytes.inline_bytes,
+                overflowed,
+            );
+            return self;
+        }
+
+        self.record_bytes(bytes)
     }
 
-    /// Returns `true` if the current context is shared
-    #[inline]
-    pub const fn is_shared(&self) -> bool {
-        self.is_shared
+    /// Adds `bytes` to the borrowed, shared or estimated bytes the context is
+    /// currently adding to, kept out of line so [`Context::add_bytes()`] stays
+    /// small enough to inline
+    fn record_bytes(&mut self, bytes: TotalSize) -> &mut Self {
+        let total = bytes.total_bytes;
+        if self.is_borrowed {
+            return self.record(TotalSize::zero().with_borrowed_bytes(total));
+        }
+
+        let shared = if self.is_shared { total } else { 0 };
+        let estimated = if self.is_estimated { total } else { 0 };
+        self.record(
+            TotalSize::new(total, bytes.excess_bytes, shared, 0)
+                .with_estimated_bytes(estimated)
+                .with_inline_bytes(bytes.inline_bytes),
+        )
     }
 
-    /// Run the given closure and mark all added allocations as shared
+    /// Adds `size` to the context's totals and reports it to all of the
+    /// context's sinks, recording an overflow if the totals overflow
     #[inline]
-    pub fn shared<F>(&mut self, with_shared: F) -> &mut Self
-    where
-        F: FnOnce(&mut Self),
-    {
-        let prev = replace(&mut self.is_shared, true);
-        with_shared(self);
-        self.is_shared = prev;
+    fn record(&mut self, size: TotalSize) -> &mut Self {
+        self.totals = match self.totals.checked_add(size) {
+            Some(totals) => totals,
+            None => {
+                self.overflowed = true;
+                self.totals.saturating_add(size)
+            }
+        };
+        if !self.sinks.is_empty() {
+            self.sinks.for_each(|sink| sink.record(size));
+        }
+
         self
     }
 
-    /// Adds one distinct allocation to the current context
+    /// Returns `count` or `usize::MAX` if computing it overflowed, recording
+    /// the overflow
     #[inline]
-    pub fn add_distinct_allocation(&mut self) -> &mut Self {
-        self.add_distinct_allocations(1)
+    fn saturate(&mut self, count: Option<usize>) -> usize {
+        count.unwrap_or_else(|| {
+            self.overflowed = true;
+            usize::MAX
+        })
     }
 
-    /// Adds `allocations` distinct allocations to the current context
+    /// Records a value whose children couldn't be measured, like a lock that
+    /// couldn't be acquired, see [`TotalSize::unmeasured_values()`]
     #[inline]
-    pub fn add_distinct_allocations(&mut self, allocations: usize) -> &mut Self {
-        self.distinct_allocations += allocations;
-        self
+    pub fn add_unmeasured(&mut self) -> &mut Self {
+        self.record(TotalSize::zero().with_unmeasured_values(1))
     }
 
     /// Adds `size` to the total bytes
     ///
     /// - Adds `size` to the shared bytes if the context is currently shared
+    /// - Adds `size` to the borrowed bytes instead if the context is currently
+    ///   borrowed
     #[inline]
     pub fn add(&mut self, size: usize) -> &mut Self {
-        self.total_bytes += size;
-        if self.is_shared {
-            self.shared_bytes += size;
-        }
+        self.add_bytes(TotalSize::total(size))
+    }
 
-        self
+    /// Adds `size` to the total and inline bytes, the bytes of a value itself
+    /// rather than of its children, see [`TotalSize::inline_bytes()`]
+    ///
+    /// - Adds `size` to the shared bytes if the context is currently shared
+    /// - Adds `size` to the borrowed bytes instead if the context is currently
+    ///   borrowed
+    #[inline]
+    pub fn add_inline(&mut self, size: usize) -> &mut Self {
+        self.add_bytes(TotalSize::total(size).with_inline_bytes(size))
     }
 
     /// Adds `size` shared bytes
     #[inline]
     pub fn add_shared(&mut self, size: usize) -> &mut Self {
-        self.shared_bytes += size;
+        if !self.is_borrowed {
+            self.record(TotalSize::new(0, 0, size, 0));
+        }
+
         self
     }
 
     /// Adds `size` to the total and excess bytes
     ///
     /// - Adds `size` to the shared bytes if the context is currently shared
+    /// - Adds `size` to the borrowed bytes instead if the context is currently
+    ///   borrowed
     #[inline]
     pub fn add_excess(&mut self, size: usize) -> &mut Self {
-        self.total_bytes += size;
-        self.excess_bytes += size;
-        if self.is_shared {
-            self.shared_bytes += size;
-        }
-
-        self
+        self.add_bytes(TotalSize::new(size, size, 0, 0))
     }
 
     /// Adds a vector-like object to the current context.
@@ -187,15 +1230,12 @@ impl Context {
     /// - Adds `len * element_size` to the total bytes
     /// - Adds `len * element_size` to the shared bytes if the context is
     ///   currently shared
+    /// - Adds `len * element_size` to the borrowed bytes instead if the
+    ///   context is currently borrowed
     #[inline]
     pub fn add_arraylike(&mut self, len: usize, element_size: usize) -> &mut Self {
-        let bytes = len * element_size;
-        self.total_bytes += bytes;
-        if self.is_shared {
-            self.shared_bytes += bytes;
-        }
-
-        self
+        let total = self.saturate(len.checked_mul(element_size));
+        self.add_bytes(TotalSize::total(total))
     }
 
     /// Adds a vector-like object to the current context.
@@ -204,6 +1244,8 @@ impl Context {
     /// - Adds `(capacity - len) * element_size` to the excess bytes
     /// - Adds `capacity * element_size` to the shared bytes if the context is
     ///   currently shared
+    /// - Adds `capacity * element_size` to the borrowed bytes instead if the
+    ///   context is currently borrowed
     #[inline]
     pub fn add_vectorlike(
         &mut self,
@@ -211,25 +1253,82 @@ impl Context {
         capacity: usize,
         element_size: usize,
     ) -> &mut Self {
-        let used = len * element_size;
-        let allocated = capacity * element_size;
-        self.total_bytes += allocated;
-        self.excess_bytes += allocated - used;
+        self.add_vectorlike_bytes(len, capacity, element_size);
+        self
+    }
+
+    /// Adds a vector-like object along with the allocation holding it to the
+    /// current context, see [`Context::add_vectorlike()`] and
+    /// [`Context::add_allocation()`]
+    ///
+    /// The allocation is `capacity * element_size` bytes, which is only
+    /// calculated once and saturates if it overflows
+    #[inline]
+    pub fn add_vectorlike_allocation(
+        &mut self,
+        len: usize,
+        capacity: usize,
+        element_size: usize,
+    ) -> &mut Self {
+        let allocated = self.add_vectorlike_bytes(len, capacity, element_size);
+        self.add_allocation(allocated)
+    }
 
-        if self.is_shared {
-            self.shared_bytes += allocated;
-        }
+    /// Adds an array-like object along with the allocation holding it to the
+    /// current context, see [`Context::add_arraylike()`] and
+    /// [`Context::add_allocation()`]
+    #[inline]
+    pub fn add_arraylike_allocation(&mut self, len: usize, element_size: usize) -> &mut Self {
+        let total = self.saturate(len.checked_mul(element_size));
+        self.add_bytes(TotalSize::total(total))
+            .add_allocation(total)
+    }
 
-        self
+    /// Adds the bytes of a vector-like object, returning the size of its
+    /// allocation
+    #[inline]
+    fn add_vectorlike_bytes(&mut self, len: usize, capacity: usize, element_size: usize) -> usize {
+        let used = self.saturate(len.checked_mul(element_size));
+        let allocated = self.saturate(capacity.checked_mul(element_size));
+
+        // A length greater than the capacity can only come from a corrupted value
+        self.overflowed |= used > allocated;
+        self.add_bytes(TotalSize::new(
+            allocated,
+            allocated.saturating_sub(used),
+            0,
+            0,
+        ));
+
+        allocated
     }
 
     /// Returns `true` and adds the given pointer to the current context if it
     /// hasn't seen it yet. Returns `false` if the current context has seen the
     /// pointer before
+    ///
+    /// Pointers seen while the context is borrowed are tracked apart from
+    /// owned ones, so reaching a shared allocation through a reference doesn't
+    /// stop its owners from counting it
     #[inline]
     pub fn insert_ptr<T: ?Sized>(&mut self, ptr: *const T) -> bool {
         // TODO: Use `pointer::addr()` whenever strict provenance stabilizes
-        self.pointers.insert(ptr as *const u8 as usize)
+        let addr = ptr as *const u8 as usize;
+
+        #[cfg(feature = "rayon")]
+        if let Some(parallel) = &self.parallel_pointers {
+            return if self.is_borrowed {
+                parallel.borrowed_pointers.insert(addr)
+            } else {
+                parallel.pointers.insert(addr)
+            };
+        }
+
+        if self.is_borrowed {
+            self.borrowed_pointers.insert(addr)
+        } else {
+            self.pointers.insert(addr)
+        }
     }
 
     /// Adds the given pointer to the current context regardless of whether it's
@@ -240,48 +1339,225 @@ impl Context {
         self
     }
 
-    /// Returns `true` if the context has seen the given pointer
+    /// Returns `true` if the context has seen the given pointer, see
+    /// [`Context::insert_ptr()`]
     #[inline]
     pub fn contains_ptr<T: ?Sized>(&self, ptr: *const T) -> bool {
         // TODO: Use `pointer::addr()` whenever strict provenance stabilizes
-        self.pointers.contains(&(ptr as *const u8 as usize))
+        let addr = ptr as *const u8 as usize;
+
+        #[cfg(feature = "rayon")]
+        if let Some(parallel) = &self.parallel_pointers {
+            return if self.is_borrowed {
+                parallel.borrowed_pointers.contains(addr)
+            } else {
+                parallel.pointers.contains(addr)
+            };
+        }
+
+        if self.is_borrowed {
+            self.borrowed_pointers.contains(addr)
+        } else {
+            self.pointers.contains(addr)
+        }
+    }
+
+    #[inline]
+    fn insert_ref<T: ?Sized>(&mut self, reference: &T) -> bool {
+        // TODO: Use `pointer::addr()` whenever strict provenance stabilizes
+        let addr = reference as *const T as *const u8 as usize;
+
+        #[cfg(feature = "rayon")]
+        if let Some(parallel) = &self.parallel_pointers {
+            return parallel.references.insert(addr);
+        }
+
+        self.references.insert(addr)
+    }
+
+    /// Merges everything `other` has recorded into the current context
+    ///
+    /// The counts of both contexts are added together and reported to the
+    /// current context's sinks as a single addition, the pointers seen by
+    /// `other` are added to the pointers seen by the current context. Named
+    /// nodes and types recorded by `other` are merged into the node and type
+    /// the current context is within, if it's recording them.
+    ///
+    /// Bytes behind pointers that both contexts have seen were counted by
+    /// both, to only count them once measure values with the same context
+    /// instead
+    pub fn merge(&mut self, other: &Self) -> &mut Self {
+        self.record(other.totals);
+        self.overflowed |= other.overflowed;
+
+        self.pointers.reserve(other.pointers.len());
+        for addr in other.pointers.iter() {
+            self.insert_ptr(addr as *const u8);
+        }
+        self.references.reserve(other.references.len());
+        for addr in other.references.iter() {
+            self.references.insert(addr);
+        }
+        self.borrowed_pointers
+            .reserve(other.borrowed_pointers.len());
+        for addr in other.borrowed_pointers.iter() {
+            self.borrowed_pointers.insert(addr);
+        }
+
+        for (&addr, &size) in &other.shared_sizes {
+            self.shared_sizes.entry(addr).or_insert(size);
+        }
+
+        if let (Some(tree), Some(other)) = (&mut self.tree, &other.tree) {
+            tree.merge(other);
+        }
+        if let (Some(types), Some(other)) = (&mut self.types, &other.types) {
+            types.merge(other);
+        }
+        if let (Some(stats), Some(other)) =
+            (&mut self.shared_allocations, &other.shared_allocations)
+        {
+            stats.merge(other);
+        }
+        if let (Some(histogram), Some(other)) = (&mut self.histogram, &other.histogram) {
+            histogram.merge(other);
+        }
+        self.sampling.merge(&other.sampling);
+
+        self
     }
 
-    // fn insert_ref<T: ?Sized>(&mut self, reference: &T) -> bool {
-    //     self.insert_ptr(reference)
-    // }
+    /// Records the shared allocation at `ptr` which is owned by `owners`
+    /// pointers of type `T`, `measure` should add the allocation and its
+    /// children
+    ///
+    /// With [`SharedAttribution::FirstOwner`] the allocation is only measured
+    /// the first time it's seen. With [`SharedAttribution::Proportional`] the
+    /// allocation is measured once and every owner is charged its fraction,
+    /// the first owner is additionally charged whatever doesn't divide evenly
+    pub(crate) fn add_shared_allocation<T, P, F>(
+        &mut self,
+        ptr: *const P,
+        owners: usize,
+        measure: F,
+    ) -> &mut Self
+    where
+        T: ?Sized,
+        P: ?Sized,
+        F: FnOnce(&mut Self),
+    {
+        let first_seen = self.insert_ptr(ptr);
+        self.sinks
+            .for_each(|sink| sink.enter_shared(ptr as *const u8 as usize));
+
+        match self.shared_attribution {
+            SharedAttribution::FirstOwner => {
+                if first_seen {
+                    self.attribute_to::<T, _>(|ctx| {
+                        let addr = ptr as *const u8 as usize;
+                        let start = ctx.total_size();
+                        let entered = match &mut ctx.shared_allocations {
+                            Some(stats) => stats.enter(addr, start),
+                            None => false,
+                        };
+
+                        measure(ctx);
+
+                        if entered {
+                            let end = ctx.total_size();
+                            if let Some(stats) = &mut ctx.shared_allocations {
+                                stats.exit(end);
+                            }
+                        }
+                    });
+                }
+            }
+
+            SharedAttribution::Proportional => {
+                let owners = owners.max(1);
+                let share = if first_seen {
+                    // Measure the allocation on its own, the nodes and types within
+                    // it aren't recorded since they're only partially charged. The
+                    // totals keep growing while it's measured so the byte budget
+                    // still applies within it
+                    let start = self.totals;
+                    let (tree, types) = (self.tree.take(), self.types.take());
+                    let sinks = take(&mut self.sinks);
+                    measure(self);
+                    let full = replace(&mut self.totals, start) - start;
+                    (self.tree, self.types, self.sinks) = (tree, types, sinks);
+
+                    self.shared_sizes.insert(ptr as *const u8 as usize, full);
+                    full.divide(owners) + full.remainder(owners)
+                } else {
+                    self.shared_sizes
+                        .get(&(ptr as *const u8 as usize))
+                        .map_or_else(TotalSize::zero, |full| full.divide(owners))
+                };
+
+                self.attribute_to::<T, _>(|ctx| {
+                    ctx.record(share);
+                });
+            }
+        }
+        self.sinks.for_each(|sink| sink.exit_shared());
+
+        self
+    }
 
+    /// Makes the context record the memory directly used by each shared
+    /// allocation it sees
     #[inline]
-    fn insert_rc<T: ?Sized>(&mut self, rc: &Rc<T>) -> bool {
-        self.insert_ptr(Rc::as_ptr(rc))
+    pub(crate) fn record_shared_allocations(&mut self) -> &mut Self {
+        if self.shared_allocations.is_none() {
+            self.shared_allocations = Some(SizeStats::default());
+        }
+
+        self
     }
 
+    /// Returns an iterator over the address of every shared allocation the
+    /// context has seen along with the memory directly used by it, excluding
+    /// any shared allocations nested within it
     #[inline]
-    fn insert_arc<T: ?Sized>(&mut self, arc: &Arc<T>) -> bool {
-        self.insert_ptr(Arc::as_ptr(arc))
+    pub(crate) fn shared_allocations(&self) -> impl Iterator<Item = (usize, TotalSize)> + '_ {
+        self.shared_allocations
+            .iter()
+            .flat_map(SizeStats::iter)
+            .map(|(addr, _, size)| (addr, size))
     }
 
     /// Returns the total size of all objects the current context has seen
     #[inline]
     pub const fn total_size(&self) -> TotalSize {
-        TotalSize::new(
-            self.total_bytes,
-            self.excess_bytes,
-            self.shared_bytes,
-            self.distinct_allocations,
-        )
+        self.totals
     }
 }
 
+/// Adds `value` to `count`, saturating and setting `overflowed` if it
+/// overflows
+///
+/// This doesn't branch, so loops that only add to counts can be vectorized
+#[inline]
+fn add_count(count: &mut usize, value: usize, overflowed: &mut bool) {
+    let (sum, overflow) = count.overflowing_add(value);
+    *count = if overflow { usize::MAX } else { sum };
+    *overflowed |= overflow;
+}
+
 impl SizeOf for Context {
     fn size_of_children(&self, context: &mut Context) {
         self.pointers.size_of_children(context);
+        self.references.size_of_children(context);
+        self.borrowed_pointers.size_of_children(context);
     }
 }
 
 /// Represents the total space taken up by an instance of a variable, including
 /// heap allocations
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct TotalSize {
     /// The total bytes used
     total_bytes: usize,
@@ -291,6 +1567,19 @@ pub struct TotalSize {
     shared_bytes: usize,
     /// The total number of distinct allocations made
     distinct_allocations: usize,
+    /// The total bytes allocators round allocations up by
+    overhead_bytes: usize,
+    /// The total bytes reachable through followed references
+    borrowed_bytes: usize,
+    /// The total bytes that came from estimates
+    estimated_bytes: usize,
+    /// The total number of values that couldn't be measured
+    unmeasured_values: usize,
+    /// The total bytes of values themselves rather than their children
+    inline_bytes: usize,
+    /// The total number of values whose children were skipped by a depth or
+    /// byte limit
+    truncated_values: usize,
 }
 
 impl TotalSize {
@@ -307,21 +1596,102 @@ impl TotalSize {
             excess_bytes,
             shared_bytes,
             distinct_allocations,
+            overhead_bytes: 0,
+            borrowed_bytes: 0,
+            estimated_bytes: 0,
+            unmeasured_values: 0,
+            inline_bytes: 0,
+            truncated_values: 0,
         }
     }
 
+    /// Sets the allocator overhead bytes, see [`TotalSize::overhead_bytes()`]
+    #[inline]
+    pub const fn with_overhead_bytes(mut self, overhead_bytes: usize) -> Self {
+        self.overhead_bytes = overhead_bytes;
+        self
+    }
+
     /// Creates a `TotalSize` with a value of zero
     #[inline]
     pub const fn zero() -> Self {
         Self::new(0, 0, 0, 0)
     }
 
+    /// Divides all counts by `divisor`, rounding down
+    #[inline]
+    const fn divide(self, divisor: usize) -> Self {
+        Self {
+            total_bytes: self.total_bytes / divisor,
+            excess_bytes: self.excess_bytes / divisor,
+            shared_bytes: self.shared_bytes / divisor,
+            distinct_allocations: self.distinct_allocations / divisor,
+            overhead_bytes: self.overhead_bytes / divisor,
+            borrowed_bytes: self.borrowed_bytes / divisor,
+            estimated_bytes: self.estimated_bytes / divisor,
+            unmeasured_values: self.unmeasured_values / divisor,
+            inline_bytes: self.inline_bytes / divisor,
+            truncated_values: self.truncated_values / divisor,
+        }
+    }
+
+    /// Multiplies all counts by `numerator / denominator`, rounding down and
+    /// saturating at `usize::MAX`
+    #[inline]
+    const fn scale(self, numerator: usize, denominator: usize) -> Self {
+        const fn scale(count: usize, numerator: usize, denominator: usize) -> usize {
+            let scaled = count as u128 * numerator as u128 / denominator as u128;
+            if scaled > usize::MAX as u128 {
+                usize::MAX
+            } else {
+                scaled as usize
+            }
+        }
+
+        Self {
+            total_bytes: scale(self.total_bytes, numerator, denominator),
+            excess_bytes: scale(self.excess_bytes, numerator, denominator),
+            shared_bytes: scale(self.shared_bytes, numerator, denominator),
+            distinct_allocations: scale(self.distinct_allocations, numerator, denominator),
+            overhead_bytes: scale(self.overhead_bytes, numerator, denominator),
+            borrowed_bytes: scale(self.borrowed_bytes, numerator, denominator),
+            estimated_bytes: scale(self.estimated_bytes, numerator, denominator),
+            unmeasured_values: scale(self.unmeasured_values, numerator, denominator),
+            inline_bytes: scale(self.inline_bytes, numerator, denominator),
+            truncated_values: scale(self.truncated_values, numerator, denominator),
+        }
+    }
+
+    /// Returns the remainders of dividing all counts by `divisor`
+    #[inline]
+    const fn remainder(self, divisor: usize) -> Self {
+        Self {
+            total_bytes: self.total_bytes % divisor,
+            excess_bytes: self.excess_bytes % divisor,
+            shared_bytes: self.shared_bytes % divisor,
+            distinct_allocations: self.distinct_allocations % divisor,
+            overhead_bytes: self.overhead_bytes % divisor,
+            borrowed_bytes: self.borrowed_bytes % divisor,
+            estimated_bytes: self.estimated_bytes % divisor,
+            unmeasured_values: self.unmeasured_values % divisor,
+            inline_bytes: self.inline_bytes % divisor,
+            truncated_values: self.truncated_values % divisor,
+        }
+    }
+
     /// Sets `total_bytes` to `total` and all others to zero
     #[inline]
     pub const fn total(total: usize) -> Self {
         Self::new(total, 0, 0, 0)
     }
 
+    /// Sets `total_bytes` and `inline_bytes` to `inline` and all others to
+    /// zero, the size of a value without any children
+    #[inline]
+    pub const fn inline(inline: usize) -> Self {
+        Self::total(inline).with_inline_bytes(inline)
+    }
+
     /// Returns the total bytes allocated
     #[inline]
     pub const fn total_bytes(&self) -> usize {
@@ -351,24 +1721,242 @@ impl TotalSize {
         self.distinct_allocations
     }
 
+    /// Sets the borrowed bytes, see [`TotalSize::borrowed_bytes()`]
+    #[inline]
+    pub const fn with_borrowed_bytes(mut self, borrowed_bytes: usize) -> Self {
+        self.borrowed_bytes = borrowed_bytes;
+        self
+    }
+
+    /// Returns the number of bytes reachable through references, these aren't
+    /// included in any of the other counts
+    ///
+    /// This is only recorded when the [`Context`] the size was collected
+    /// with follows references, see [`Context::set_follow_references()`]
+    #[inline]
+    pub const fn borrowed_bytes(&self) -> usize {
+        self.borrowed_bytes
+    }
+
+    /// Sets the estimated bytes, see [`TotalSize::estimated_bytes()`]
+    #[inline]
+    pub const fn with_estimated_bytes(mut self, estimated_bytes: usize) -> Self {
+        self.estimated_bytes = estimated_bytes;
+        self
+    }
+
+    /// Returns the number of bytes that came from estimates, like the modeled
+    /// nodes of a `BTreeMap` or the extrapolated elements of a sampled
+    /// collection
+    ///
+    /// Estimated bytes are included within the total bytes, see
+    /// [`TotalSize::exact_bytes()`] for the rest
+    #[inline]
+    pub const fn estimated_bytes(&self) -> usize {
+        self.estimated_bytes
+    }
+
+    /// Returns the number of bytes that came from exact sources, calculated
+    /// by `total_bytes - estimated_bytes`
+    #[inline]
+    pub const fn exact_bytes(&self) -> usize {
+        self.total_bytes.saturating_sub(self.estimated_bytes)
+    }
+
+    /// Sets the number of unmeasured values, see
+    /// [`TotalSize::unmeasured_values()`]
+    #[inline]
+    pub const fn with_unmeasured_values(mut self, unmeasured_values: usize) -> Self {
+        self.unmeasured_values = unmeasured_values;
+        self
+    }
+
+    /// Returns the number of values whose children couldn't be measured, like
+    /// locks that couldn't be acquired (see [`LockPolicy`]) or `RefCell`s that
+    /// were mutably borrowed
+    ///
+    /// When this isn't zero the other counts are missing the children of those
+    /// values
+    #[inline]
+    pub const fn unmeasured_values(&self) -> usize {
+        self.unmeasured_values
+    }
+
+    /// Sets the number of truncated values, see
+    /// [`TotalSize::truncated_values()`]
+    #[inline]
+    pub const fn with_truncated_values(mut self, truncated_values: usize) -> Self {
+        self.truncated_values = truncated_values;
+        self
+    }
+
+    /// Returns the number of values whose children were skipped because the
+    /// [`Context`] reached its maximum depth or exhausted its byte budget, see
+    /// [`Context::set_max_depth()`] and [`Context::set_byte_budget()`]
+    #[inline]
+    pub const fn truncated_values(&self) -> usize {
+        self.truncated_values
+    }
+
+    /// Returns `true` if any values were skipped by a depth or byte limit, in
+    /// which case the other counts are lower bounds
+    #[inline]
+    pub const fn is_truncated(&self) -> bool {
+        self.truncated_values != 0
+    }
+
+    /// Sets the inline bytes, see [`TotalSize::inline_bytes()`]
+    #[inline]
+    pub const fn with_inline_bytes(mut self, inline_bytes: usize) -> Self {
+        self.inline_bytes = inline_bytes;
+        self
+    }
+
+    /// Returns the number of bytes taken up by measured values themselves,
+    /// e.g. the pointer, length and capacity of a `Vec`
+    ///
+    /// Inline bytes are included within the total bytes, see
+    /// [`TotalSize::heap_bytes()`] for the rest
+    #[inline]
+    pub const fn inline_bytes(&self) -> usize {
+        self.inline_bytes
+    }
+
+    /// Returns the number of bytes owned by measured values, e.g. the elements
+    /// of a `Vec`, calculated by `total_bytes - inline_bytes`
+    #[inline]
+    pub const fn heap_bytes(&self) -> usize {
+        self.total_bytes.saturating_sub(self.inline_bytes)
+    }
+
+    /// Returns the number of bytes allocators round allocations up by
+    ///
+    /// This is only recorded when the [`Context`] the size was collected
+    /// with has an [`AllocatorModel`]
+    #[inline]
+    pub const fn overhead_bytes(&self) -> usize {
+        self.overhead_bytes
+    }
+
+    /// Returns the total bytes including allocator overhead, calculated by
+    /// `total_bytes + overhead_bytes`
+    ///
+    /// Where [`TotalSize::total_bytes()`] reports the bytes that were
+    /// requested, this reports the bytes allocators actually handed out
+    #[inline]
+    pub const fn rounded_bytes(&self) -> usize {
+        self.total_bytes.saturating_add(self.overhead_bytes)
+    }
+
     /// Return the total used bytes, calculated by `total_bytes - excess_bytes`
     #[inline]
     pub const fn used_bytes(&self) -> usize {
-        self.total_bytes - self.excess_bytes
+        self.total_bytes.saturating_sub(self.excess_bytes)
+    }
+
+    /// Adds `rhs` to every count, returning `None` if any of them would
+    /// overflow
+    #[inline]
+    pub const fn checked_add(self, rhs: Self) -> Option<Self> {
+        // `?` isn't allowed within const functions
+        macro_rules! checked_add {
+            ($field:ident) => {
+                match self.$field.checked_add(rhs.$field) {
+                    Some(count) => count,
+                    None => return None,
+                }
+            };
+        }
+
+        Some(Self {
+            total_bytes: checked_add!(total_bytes),
+            excess_bytes: checked_add!(excess_bytes),
+            shared_bytes: checked_add!(shared_bytes),
+            distinct_allocations: checked_add!(distinct_allocations),
+            overhead_bytes: checked_add!(overhead_bytes),
+            borrowed_bytes: checked_add!(borrowed_bytes),
+            estimated_bytes: checked_add!(estimated_bytes),
+            unmeasured_values: checked_add!(unmeasured_values),
+            inline_bytes: checked_add!(inline_bytes),
+            truncated_values: checked_add!(truncated_values),
+        })
+    }
+
+    /// Subtracts `rhs` from every count, returning `None` if any of them would
+    /// underflow
+    #[inline]
+    pub const fn checked_sub(self, rhs: Self) -> Option<Self> {
+        // `?` isn't allowed within const functions
+        macro_rules! checked_sub {
+            ($field:ident) => {
+                match self.$field.checked_sub(rhs.$field) {
+                    Some(count) => count,
+                    None => return None,
+                }
+            };
+        }
+
+        Some(Self {
+            total_bytes: checked_sub!(total_bytes),
+            excess_bytes: checked_sub!(excess_bytes),
+            shared_bytes: checked_sub!(shared_bytes),
+            distinct_allocations: checked_sub!(distinct_allocations),
+            overhead_bytes: checked_sub!(overhead_bytes),
+            borrowed_bytes: checked_sub!(borrowed_bytes),
+            estimated_bytes: checked_sub!(estimated_bytes),
+            unmeasured_values: checked_sub!(unmeasured_values),
+            inline_bytes: checked_sub!(inline_bytes),
+            truncated_values: checked_sub!(truncated_values),
+        })
+    }
+
+    /// Subtracts `rhs` from every count, saturating at zero
+    #[inline]
+    pub const fn saturating_sub(self, rhs: Self) -> Self {
+        Self {
+            total_bytes: self.total_bytes.saturating_sub(rhs.total_bytes),
+            excess_bytes: self.excess_bytes.saturating_sub(rhs.excess_bytes),
+            shared_bytes: self.shared_bytes.saturating_sub(rhs.shared_bytes),
+            distinct_allocations: self
+                .distinct_allocations
+                .saturating_sub(rhs.distinct_allocations),
+            overhead_bytes: self.overhead_bytes.saturating_sub(rhs.overhead_bytes),
+            borrowed_bytes: self.borrowed_bytes.saturating_sub(rhs.borrowed_bytes),
+            estimated_bytes: self.estimated_bytes.saturating_sub(rhs.estimated_bytes),
+            unmeasured_values: self.unmeasured_values.saturating_sub(rhs.unmeasured_values),
+            inline_bytes: self.inline_bytes.saturating_sub(rhs.inline_bytes),
+            truncated_values: self.truncated_values.saturating_sub(rhs.truncated_values),
+        }
+    }
+
+    /// Adds `rhs` to every count, saturating at `usize::MAX`
+    #[inline]
+    pub const fn saturating_add(self, rhs: Self) -> Self {
+        Self {
+            total_bytes: self.total_bytes.saturating_add(rhs.total_bytes),
+            excess_bytes: self.excess_bytes.saturating_add(rhs.excess_bytes),
+            shared_bytes: self.shared_bytes.saturating_add(rhs.shared_bytes),
+            distinct_allocations: self
+                .distinct_allocations
+                .saturating_add(rhs.distinct_allocations),
+            overhead_bytes: self.overhead_bytes.saturating_add(rhs.overhead_bytes),
+            borrowed_bytes: self.borrowed_bytes.saturating_add(rhs.borrowed_bytes),
+            estimated_bytes: self.estimated_bytes.saturating_add(rhs.estimated_bytes),
+            unmeasured_values: self.unmeasured_values.saturating_add(rhs.unmeasured_values),
+            inline_bytes: self.inline_bytes.saturating_add(rhs.inline_bytes),
+            truncated_values: self.truncated_values.saturating_add(rhs.truncated_values),
+        }
     }
 }
 
 impl Add for TotalSize {
     type Output = Self;
 
+    /// Adds every count, saturating at `usize::MAX`, see
+    /// [`TotalSize::saturating_add()`]
     #[inline]
     fn add(self, rhs: Self) -> Self::Output {
-        Self {
-            total_bytes: self.total_bytes + rhs.total_bytes,
-            excess_bytes: self.excess_bytes + rhs.excess_bytes,
-            shared_bytes: self.shared_bytes + rhs.shared_bytes,
-            distinct_allocations: self.distinct_allocations + rhs.distinct_allocations,
-        }
+        self.saturating_add(rhs)
     }
 }
 
@@ -382,14 +1970,11 @@ impl AddAssign for TotalSize {
 impl Sub for TotalSize {
     type Output = Self;
 
+    /// Subtracts every count, saturating at zero, see
+    /// [`TotalSize::checked_sub()`] and [`TotalSize::saturating_sub()`]
     #[inline]
     fn sub(self, rhs: Self) -> Self::Output {
-        Self {
-            total_bytes: self.total_bytes - rhs.total_bytes,
-            excess_bytes: self.excess_bytes - rhs.excess_bytes,
-            shared_bytes: self.shared_bytes - rhs.shared_bytes,
-            distinct_allocations: self.distinct_allocations - rhs.distinct_allocations,
-        }
+        self.saturating_sub(rhs)
     }
 }
 
@@ -0,0 +1,19 @@
+/// How a [`Context`](crate::Context) measures the contents of locks (`Mutex`
+/// and `RwLock`)
+///
+/// Values that can't be measured, like locks that are already held or
+/// poisoned and `RefCell`s that are mutably borrowed, are recorded within
+/// [`TotalSize::unmeasured_values()`](crate::TotalSize::unmeasured_values)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum LockPolicy {
+    /// Locks are acquired by blocking until they're available, this is the
+    /// default
+    ///
+    /// This deadlocks if the measuring thread already holds one of the locks
+    /// it measures
+    #[default]
+    Block,
+    /// Locks are only measured if they can be acquired without blocking
+    /// (`try_lock` and `try_read`), any other locks are skipped
+    TryLock,
+}
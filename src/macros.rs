@@ -6,6 +6,8 @@ macro_rules! impl_total_size_childless {
             impl $(<$($generic),*>)? $crate::SizeOf for $($ident)::+ $(<$($generic),*>)? {
                 #[inline]
                 fn size_of_children(&self, _context: &mut $crate::Context) {}
+
+                const IS_CHILDLESS: bool = true;
             }
         )*
     };
@@ -26,6 +28,8 @@ macro_rules! impl_tuple {
                     let ($($elem,)*) = self;
                     $($elem.size_of_children(context);)*
                 }
+
+                const IS_CHILDLESS: bool = true $(&& $elem::IS_CHILDLESS)*;
             }
         )*
     };
@@ -33,8 +37,9 @@ macro_rules! impl_tuple {
 
 // Implement SizeOf for a handful of function pointers (up to 16 args)
 macro_rules! impl_function_ptrs {
-    ($($cconv:literal),* $(,)?) => {
+    ($($(#[$meta:meta])* $cconv:literal),* $(,)?) => {
         $(
+            $(#[$meta])*
             impl_function_ptrs! {
                 @inner $cconv
                 (),
@@ -66,6 +71,8 @@ macro_rules! impl_function_ptrs {
             impl<$($ty,)* U> $crate::SizeOf for extern $cconv fn($($ty),*) -> U {
                 #[inline]
                 fn size_of_children(&self, _context: &mut $crate::Context) {}
+
+                const IS_CHILDLESS: bool = true;
             }
         )*
     };
@@ -0,0 +1,266 @@
+use crate::{Context, DynSizeOf, HumanBytes, SizeOf, TotalSize};
+use alloc::{
+    borrow::Cow,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{
+    fmt::{self, Display, Write},
+    slice,
+};
+
+/// A gauge written by [`ComponentReport::write_openmetrics()`]
+struct Gauge {
+    name: &'static str,
+    unit: Option<&'static str>,
+    help: &'static str,
+    value: fn(&TotalSize) -> usize,
+}
+
+const GAUGES: [Gauge; 4] = [
+    Gauge {
+        name: "total_bytes",
+        unit: Some("bytes"),
+        help: "Total bytes owned by the component",
+        value: TotalSize::total_bytes,
+    },
+    Gauge {
+        name: "excess_bytes",
+        unit: Some("bytes"),
+        help: "Bytes allocated by the component but not in use",
+        value: TotalSize::excess_bytes,
+    },
+    Gauge {
+        name: "shared_bytes",
+        unit: Some("bytes"),
+        help: "Bytes owned by the component through shared pointers",
+        value: TotalSize::shared_bytes,
+    },
+    Gauge {
+        name: "distinct_allocations",
+        unit: None,
+        help: "Distinct allocations owned by the component",
+        value: TotalSize::distinct_allocations,
+    },
+];
+
+/// Measures each of the given named components on its own, e.g. to expose the
+/// size of every part of a service as metrics, see
+/// [`ComponentReport::write_openmetrics()`]
+///
+/// Unlike [`size_of_values()`](crate::size_of_values) every component gets its
+/// own [`Context`], so allocations shared between components are counted once
+/// for each of them
+///
+/// ```rust
+/// use size_of::DynSizeOf;
+///
+/// let cache: Vec<u64> = Vec::with_capacity(1024);
+/// let names = vec![String::from("name")];
+///
+/// let report = size_of::component_sizes([
+///     ("cache", &cache as &dyn DynSizeOf),
+///     ("names", &names as &dyn DynSizeOf),
+/// ]);
+///
+/// let metrics = report.to_openmetrics("service_memory");
+/// assert!(metrics.contains("service_memory_excess_bytes{component=\"cache\"} 8192\n"));
+/// assert!(metrics.ends_with("# EOF\n"));
+/// ```
+pub fn component_sizes<'a, I, N>(components: I) -> ComponentReport
+where
+    I: IntoIterator<Item = (N, &'a dyn DynSizeOf)>,
+    N: Into<Cow<'static, str>>,
+{
+    let components = components
+        .into_iter()
+        .map(|(name, component)| {
+            let mut context = Context::new();
+            component.dyn_size_of_with_context(&mut context);
+
+            ComponentSize::new(name, context.total_size())
+        })
+        .collect();
+
+    ComponentReport::new(components)
+}
+
+/// The sizes of a set of components, created by [`component_sizes()`]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ComponentReport {
+    components: Vec<ComponentSize>,
+}
+
+impl ComponentReport {
+    /// Creates a new report from the given components
+    #[inline]
+    pub const fn new(components: Vec<ComponentSize>) -> Self {
+        Self { components }
+    }
+
+    /// Returns the number of components within the report
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.components.len()
+    }
+
+    /// Returns `true` if the report contains no components
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.components.is_empty()
+    }
+
+    /// Returns the component with the given name (if it exists)
+    #[inline]
+    pub fn get(&self, name: &str) -> Option<&ComponentSize> {
+        self.components
+            .iter()
+            .find(|component| component.name() == name)
+    }
+
+    /// Returns an iterator over all components within the report in the order
+    /// they were given
+    #[inline]
+    pub fn iter(&self) -> slice::Iter<'_, ComponentSize> {
+        self.components.iter()
+    }
+
+    /// Writes the report in the [OpenMetrics] text format, the total, excess
+    /// and shared bytes and the distinct allocations of every component are
+    /// written as gauges named `{prefix}_total_bytes` and so on with a
+    /// `component` label holding the component's name
+    ///
+    /// `prefix` should be a valid metric name, the output is terminated by
+    /// `# EOF` so it can be served as-is
+    ///
+    /// [OpenMetrics]: https://openmetrics.io
+    pub fn write_openmetrics<W>(&self, writer: &mut W, prefix: &str) -> fmt::Result
+    where
+        W: Write,
+    {
+        for gauge in &GAUGES {
+            let name = gauge.name;
+            writeln!(writer, "# TYPE {prefix}_{name} gauge")?;
+            if let Some(unit) = gauge.unit {
+                writeln!(writer, "# UNIT {prefix}_{name} {unit}")?;
+            }
+            writeln!(writer, "# HELP {prefix}_{name} {}", gauge.help)?;
+
+            for component in &self.components {
+                write!(writer, "{prefix}_{name}{{component=\"")?;
+                write_label_value(writer, component.name())?;
+                writeln!(writer, "\"}} {}", (gauge.value)(&component.size))?;
+            }
+        }
+
+        writeln!(writer, "# EOF")
+    }
+
+    /// Returns the report in the OpenMetrics text format, see
+    /// [`ComponentReport::write_openmetrics()`]
+    pub fn to_openmetrics(&self, prefix: &str) -> String {
+        let mut metrics = String::new();
+        self.write_openmetrics(&mut metrics, prefix)
+            .expect("writing to a string can't fail");
+
+        metrics
+    }
+}
+
+/// Escapes backslashes, quotes and newlines within label values
+fn write_label_value<W>(writer: &mut W, value: &str) -> fmt::Result
+where
+    W: Write,
+{
+    for char in value.chars() {
+        match char {
+            '\\' => writer.write_str("\\\\")?,
+            '"' => writer.write_str("\\\"")?,
+            '\n' => writer.write_str("\\n")?,
+            char => writer.write_char(char)?,
+        }
+    }
+
+    Ok(())
+}
+
+impl<'a> IntoIterator for &'a ComponentReport {
+    type Item = &'a ComponentSize;
+    type IntoIter = slice::Iter<'a, ComponentSize>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl Display for ComponentReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>12} {:>12} {:>12} {:>12}  component",
+            "total", "excess", "shared", "allocations",
+        )?;
+
+        for component in &self.components {
+            writeln!(
+                f,
+                "{:>12} {:>12} {:>12} {:>12}  {}",
+                HumanBytes::from(component.size.total_bytes()).to_string(),
+                HumanBytes::from(component.size.excess_bytes()).to_string(),
+                HumanBytes::from(component.size.shared_bytes()).to_string(),
+                component.size.distinct_allocations(),
+                component.name,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl SizeOf for ComponentReport {
+    fn size_of_children(&self, context: &mut Context) {
+        self.components.size_of_children(context);
+    }
+}
+
+/// The size of a single component within a [`ComponentReport`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ComponentSize {
+    name: Cow<'static, str>,
+    size: TotalSize,
+}
+
+impl ComponentSize {
+    /// Creates a new `ComponentSize`
+    #[inline]
+    pub fn new<N>(name: N, size: TotalSize) -> Self
+    where
+        N: Into<Cow<'static, str>>,
+    {
+        Self {
+            name: name.into(),
+            size,
+        }
+    }
+
+    /// Returns the name of the component
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the size of the component
+    #[inline]
+    pub const fn size(&self) -> TotalSize {
+        self.size
+    }
+}
+
+impl SizeOf for ComponentSize {
+    fn size_of_children(&self, context: &mut Context) {
+        self.name.size_of_children(context);
+    }
+}
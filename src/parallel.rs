@@ -0,0 +1,389 @@
+#![cfg(feature = "rayon")]
+
+use crate::{
+    collections::size_of_vec,
+    pointer_set::{self, PointerSet},
+    std_impls::hashmap::size_of_hashmap,
+    AllocatorModel, Context, LockPolicy, SharedAttribution, SizeOf, TotalSize, WeakPolicy,
+};
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
+use core::mem::{size_of_val, take};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+/// The number of elements a collection needs before its elements are visited
+/// in parallel
+const PARALLEL_THRESHOLD: usize = 1024;
+
+/// The number of shards within a [`SharedPointers`], must be a power of two
+const SHARDS: usize = 64;
+
+/// Types whose children can be measured in parallel with rayon
+///
+/// Slices, `Vec`s and hash maps measure their elements with
+/// [`ParSizeOf::par_size_of_children()`], so the elements of nested collections
+/// are measured in parallel even when the outer collection is small. Types
+/// that don't contain any large collections can implement `ParSizeOf` with an
+/// empty impl, which measures their children on the current thread
+///
+/// ```rust
+/// use size_of::{ParSizeOf, SizeOf};
+///
+/// let strings: Vec<String> = (0..10_000).map(|i| i.to_string()).collect();
+/// assert_eq!(strings.par_size_of(), strings.size_of());
+/// ```
+pub trait ParSizeOf: SizeOf + Sync {
+    /// Gets the total size of the current value, measuring its children in
+    /// parallel
+    #[inline]
+    fn par_size_of(&self) -> TotalSize {
+        let mut context = Context::new();
+        self.par_size_of_with_context(&mut context);
+        context.total_size()
+    }
+
+    /// Adds the size of the current value to the given [`Context`], see
+    /// [`SizeOf::size_of_with_context()`]
+    #[inline]
+    fn par_size_of_with_context(&self, context: &mut Context) {
+        context.attribute_to::<Self, _>(|ctx| {
+            ctx.add_inline(size_of_val(self));
+            self.par_size_of_children(ctx);
+        });
+    }
+
+    /// Gets the size of all "children" owned by this value in parallel, see
+    /// [`SizeOf::size_of_children()`]
+    ///
+    /// Measures the children on the current thread by default
+    #[inline]
+    fn par_size_of_children(&self, context: &mut Context) {
+        self.size_of_children(context);
+    }
+}
+
+// Measure the children of these types on the current thread
+macro_rules! impl_par_size_of {
+    ($($type:ty),* $(,)?) => {
+        $(impl ParSizeOf for $type {})*
+    };
+}
+
+impl_par_size_of! {
+    (),
+    bool,
+    char,
+    u8,
+    i8,
+    u16,
+    i16,
+    u32,
+    i32,
+    u64,
+    i64,
+    u128,
+    i128,
+    usize,
+    isize,
+    f32,
+    f64,
+    str,
+    String,
+}
+
+// Wrappers measure their values on the current thread
+impl<T> ParSizeOf for &T where T: SizeOf + Sync + ?Sized {}
+
+impl<T> ParSizeOf for Box<T> where T: SizeOf + Sync + ?Sized {}
+
+impl<T> ParSizeOf for Arc<T> where T: SizeOf + Send + Sync + ?Sized {}
+
+impl<T> ParSizeOf for Option<T> where T: SizeOf + Sync {}
+
+impl<A, B> ParSizeOf for (A, B)
+where
+    A: SizeOf + Sync,
+    B: SizeOf + Sync,
+{
+}
+
+impl<T> ParSizeOf for [T]
+where
+    T: ParSizeOf,
+{
+    #[inline]
+    fn par_size_of_children(&self, context: &mut Context) {
+        if !T::IS_CHILDLESS {
+            context
+                .par_visit_inline_elements(self, |ctx, element| element.par_size_of_children(ctx));
+        }
+    }
+}
+
+impl<T> ParSizeOf for Vec<T>
+where
+    T: ParSizeOf,
+{
+    fn par_size_of_children(&self, context: &mut Context) {
+        size_of_vec(self, context, |ctx| {
+            ctx.par_visit_elements(self, |ctx, element| element.par_size_of_children(ctx));
+        });
+    }
+}
+
+impl<K, V, S> ParSizeOf for HashMap<K, V, S>
+where
+    K: ParSizeOf,
+    V: ParSizeOf,
+    S: SizeOf + Sync,
+{
+    fn par_size_of_children(&self, context: &mut Context) {
+        size_of_hashmap::<Self, K, V, S, _>(
+            "HashMap",
+            self.len(),
+            self.capacity(),
+            self.hasher(),
+            context,
+            |ctx| {
+                ctx.par_visit_elements(self, |ctx, (key, value)| {
+                    ctx.named("keys", |ctx| key.par_size_of_children(ctx))
+                        .named("values", |ctx| value.par_size_of_children(ctx));
+                });
+            },
+        );
+    }
+}
+
+impl Context {
+    /// Visits the given elements of a collection with `visit` in parallel, see
+    /// [`Context::visit_elements()`]
+    ///
+    /// Every thread visits its elements with its own context, which are then
+    /// merged into the current one (see [`Context::merge()`]). Shared pointers
+    /// are deduplicated across all threads, so every `Rc` or `Arc` is still only
+    /// counted once, the same goes for references when the context follows
+    /// them. Sinks only see the merged totals of the elements visited
+    /// in parallel instead of every individual addition
+    ///
+    /// The elements are visited on the current thread when there are only a
+    /// few of them, when the context samples collections, when it has a
+    /// maximum depth or byte budget, when it attributes shared allocations
+    /// proportionally or when it's already visiting the elements of another
+    /// collection in parallel
+    pub fn par_visit_elements<I, F>(&mut self, elements: I, visit: F) -> &mut Self
+    where
+        I: IntoParallelIterator + IntoIterator<Item = <I as IntoParallelIterator>::Item> + Clone,
+        <I as IntoIterator>::IntoIter: ExactSizeIterator,
+        F: Fn(&mut Self, <I as IntoParallelIterator>::Item) + Sync + Send,
+    {
+        self.descend(|ctx| {
+            ctx.par_visit_inline_elements(elements, visit);
+        })
+    }
+
+    /// Visits the given elements of an inline collection in parallel without
+    /// descending, see [`Context::par_visit_elements()`]
+    pub(crate) fn par_visit_inline_elements<I, F>(&mut self, elements: I, visit: F) -> &mut Self
+    where
+        I: IntoParallelIterator + IntoIterator<Item = <I as IntoParallelIterator>::Item> + Clone,
+        <I as IntoIterator>::IntoIter: ExactSizeIterator,
+        F: Fn(&mut Self, <I as IntoParallelIterator>::Item) + Sync + Send,
+    {
+        let sequential = elements.clone().into_iter().len() < PARALLEL_THRESHOLD
+            || self.sample_size.is_some()
+            || self.max_depth.is_some()
+            || self.byte_budget.is_some()
+            || self.shared_attribution == SharedAttribution::Proportional
+            || self.parallel_pointers.is_some();
+        if sequential {
+            return self.visit_inline_elements(elements, visit);
+        }
+
+        // Every thread shares the pointers seen so far along with any they see
+        let pointers = Arc::new(ParallelPointers::take(self));
+        let fork = Fork::new(self, &pointers);
+
+        let merged = elements
+            .into_par_iter()
+            .fold(
+                || fork.context(),
+                |mut context, element| {
+                    visit(&mut context, element);
+                    context
+                },
+            )
+            .reduce_with(|mut merged, context| {
+                merged.merge(&context);
+                merged
+            })
+            .map(|mut merged| {
+                merged.parallel_pointers = None;
+                merged
+            });
+        drop(fork);
+
+        Arc::into_inner(pointers)
+            .expect("every forked context has been dropped")
+            .restore(self);
+        if let Some(merged) = merged {
+            self.merge(&merged);
+        }
+
+        self
+    }
+}
+
+/// The settings used to create the context of each thread that's visiting
+/// elements in parallel
+struct Fork {
+    is_shared: bool,
+    is_borrowed: bool,
+    is_estimated: bool,
+    follow_references: bool,
+    weak_policy: WeakPolicy,
+    lock_policy: LockPolicy,
+    allocator: Option<Arc<dyn AllocatorModel>>,
+    record_tree: bool,
+    record_types: bool,
+    record_shared_allocations: bool,
+    record_allocation_histogram: bool,
+    pointers: Arc<ParallelPointers>,
+}
+
+impl Fork {
+    fn new(context: &Context, pointers: &Arc<ParallelPointers>) -> Self {
+        Self {
+            is_shared: context.is_shared,
+            is_borrowed: context.is_borrowed,
+            is_estimated: context.is_estimated,
+            follow_references: context.follow_references,
+            weak_policy: context.weak_policy,
+            lock_policy: context.lock_policy,
+            allocator: context.allocator.clone(),
+            record_tree: context.tree.is_some(),
+            record_types: context.types.is_some(),
+            record_shared_allocations: context.shared_allocations.is_some(),
+            record_allocation_histogram: context.histogram.is_some(),
+            pointers: pointers.clone(),
+        }
+    }
+
+    fn context(&self) -> Context {
+        let mut context = Context {
+            is_shared: self.is_shared,
+            is_borrowed: self.is_borrowed,
+            is_estimated: self.is_estimated,
+            follow_references: self.follow_references,
+            weak_policy: self.weak_policy,
+            lock_policy: self.lock_policy,
+            allocator: self.allocator.clone(),
+            parallel_pointers: Some(self.pointers.clone()),
+            ..Context::default()
+        };
+
+        if self.record_tree {
+            context.record_tree();
+        }
+        if self.record_types {
+            context.record_types();
+        }
+        if self.record_shared_allocations {
+            context.record_shared_allocations();
+        }
+        if self.record_allocation_histogram {
+            context.record_allocation_histogram();
+        }
+
+        context
+    }
+}
+
+/// The pointers and references seen by every thread that's visiting elements
+/// in parallel, each of a context's pointer sets is shared between them
+#[derive(Debug)]
+pub(crate) struct ParallelPointers {
+    pub(crate) pointers: SharedPointers,
+    pub(crate) references: SharedPointers,
+    pub(crate) borrowed_pointers: SharedPointers,
+}
+
+impl ParallelPointers {
+    /// Moves the pointer sets of `context` into sets shared between threads
+    fn take(context: &mut Context) -> Self {
+        Self {
+            pointers: SharedPointers::new(take(&mut context.pointers)),
+            references: SharedPointers::new(take(&mut context.references)),
+            borrowed_pointers: SharedPointers::new(take(&mut context.borrowed_pointers)),
+        }
+    }
+
+    /// Moves the pointers seen by every thread back into `context`
+    fn restore(self, context: &mut Context) {
+        context.pointers = self.pointers.into_pointer_set();
+        context.references = self.references.into_pointer_set();
+        context.borrowed_pointers = self.borrowed_pointers.into_pointer_set();
+    }
+}
+
+/// A pointer set shared by every thread that's visiting elements in parallel
+///
+/// Pointers are split between a number of shards so that threads rarely
+/// contend over the same lock
+#[derive(Debug)]
+pub(crate) struct SharedPointers {
+    /// The pointers that were seen before visiting elements in parallel
+    seen: PointerSet,
+    shards: Vec<Mutex<PointerSet>>,
+}
+
+impl SharedPointers {
+    fn new(seen: PointerSet) -> Self {
+        Self {
+            seen,
+            shards: (0..SHARDS).map(|_| Mutex::default()).collect(),
+        }
+    }
+
+    /// Inserts an address into the set, returns `true` if no thread has seen
+    /// it yet
+    #[inline]
+    pub(crate) fn insert(&self, addr: usize) -> bool {
+        !self.seen.contains(addr) && self.shard(addr).insert(addr)
+    }
+
+    /// Returns `true` if any thread has seen the given address
+    #[inline]
+    pub(crate) fn contains(&self, addr: usize) -> bool {
+        self.seen.contains(addr) || self.shard(addr).contains(addr)
+    }
+
+    #[inline]
+    fn shard(&self, addr: usize) -> MutexGuard<'_, PointerSet> {
+        // The pointer sets within each shard use the high bits of the hash, so
+        // the shard is picked with a mix of its lower bits
+        let hash = pointer_set::hash(addr, usize::BITS);
+        let shard = (hash ^ (hash >> 29)) & (SHARDS - 1);
+
+        // Sets can't be left in an invalid state, so poisoning doesn't matter
+        self.shards[shard]
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Combines the pointers seen by every thread into a single set
+    fn into_pointer_set(self) -> PointerSet {
+        let mut pointers = self.seen;
+        for shard in self.shards {
+            let shard = shard.into_inner().unwrap_or_else(PoisonError::into_inner);
+            pointers.reserve(shard.len());
+            shard.iter().for_each(|addr| {
+                pointers.insert(addr);
+            });
+        }
+
+        pointers
+    }
+}
@@ -0,0 +1,230 @@
+use crate::{Context, SizeOf};
+use alloc::{vec, vec::Vec};
+use core::{
+    fmt::{self, Debug},
+    mem::size_of,
+};
+
+/// The set of pointer addresses a [`Context`] has seen
+///
+/// An open-addressing hash set using linear probing. Addresses are spread
+/// across the table with fibonacci hashing, which is cheap and does well with
+/// the aligned and mostly-sequential addresses handed out by allocators. Empty
+/// slots are marked with a zero so the null address is tracked separately
+#[derive(Default)]
+pub(crate) struct PointerSet {
+    /// The table of addresses, its length is always zero or a power of two
+    slots: Vec<usize>,
+    /// The number of non-zero addresses within the table
+    len: usize,
+    /// Whether the set contains the null address
+    contains_null: bool,
+}
+
+impl PointerSet {
+    /// The smallest number of slots an allocated table has
+    const MIN_SLOTS: usize = 16;
+
+    /// Creates a set that can hold at least `capacity` addresses without
+    /// reallocating
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        let mut set = Self::default();
+        set.reserve(capacity);
+        set
+    }
+
+    /// Returns the number of addresses within the set
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        self.len + self.contains_null as usize
+    }
+
+    /// Reserves space for at least `additional` more addresses
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        // Reserving is only a hint, so capacities that can't exist are ignored
+        let required = self.len.checked_add(additional).and_then(Self::slots_for);
+        if let Some(required) = required.filter(|&required| required > self.slots.len()) {
+            self.resize(required);
+        }
+    }
+
+    /// Removes every address from the set while keeping its allocated table
+    pub(crate) fn clear(&mut self) {
+        self.slots.fill(0);
+        self.len = 0;
+        self.contains_null = false;
+    }
+
+    /// Inserts an address into the set, returns `true` if it wasn't already
+    /// in the set
+    #[inline]
+    pub(crate) fn insert(&mut self, addr: usize) -> bool {
+        if addr == 0 {
+            return !core::mem::replace(&mut self.contains_null, true);
+        }
+
+        // Keep the table at most 3/4 full
+        if (self.len + 1) * 4 > self.slots.len() * 3 {
+            let slots = Self::slots_for(self.len + 1).expect("pointer set capacity overflow");
+            self.resize(slots);
+        }
+
+        let mask = self.slots.len() - 1;
+        let mut idx = self.index_of(addr);
+        loop {
+            match self.slots[idx] {
+                0 => {
+                    self.slots[idx] = addr;
+                    self.len += 1;
+                    return true;
+                }
+                slot if slot == addr => return false,
+                _ => idx = (idx + 1) & mask,
+            }
+        }
+    }
+
+    /// Returns `true` if the set contains the given address
+    #[inline]
+    pub(crate) fn contains(&self, addr: usize) -> bool {
+        if addr == 0 {
+            return self.contains_null;
+        } else if self.slots.is_empty() {
+            return false;
+        }
+
+        let mask = self.slots.len() - 1;
+        let mut idx = self.index_of(addr);
+        loop {
+            match self.slots[idx] {
+                0 => return false,
+                slot if slot == addr => return true,
+                _ => idx = (idx + 1) & mask,
+            }
+        }
+    }
+
+    /// Returns an iterator over all addresses within the set in an arbitrary
+    /// order
+    pub(crate) fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.contains_null
+            .then_some(0)
+            .into_iter()
+            .chain(self.slots.iter().copied().filter(|&addr| addr != 0))
+    }
+
+    /// Returns the number of slots required to hold `len` addresses
+    fn slots_for(len: usize) -> Option<usize> {
+        if len == 0 {
+            Some(0)
+        } else {
+            // `len * 4 / 3` without overflowing
+            len.checked_add(len / 3)?
+                .checked_add(1)?
+                .checked_next_power_of_two()
+                .map(|slots| slots.max(Self::MIN_SLOTS))
+        }
+    }
+
+    /// Returns the slot an address should be placed in
+    #[inline]
+    fn index_of(&self, addr: usize) -> usize {
+        hash(addr, self.slots.len().trailing_zeros())
+    }
+
+    /// Moves all addresses into a new table with `slots` slots
+    fn resize(&mut self, slots: usize) {
+        let old = core::mem::replace(&mut self.slots, vec![0; slots]);
+        let mask = slots - 1;
+
+        for addr in old.into_iter().filter(|&addr| addr != 0) {
+            let mut idx = self.index_of(addr);
+            while self.slots[idx] != 0 {
+                idx = (idx + 1) & mask;
+            }
+            self.slots[idx] = addr;
+        }
+    }
+}
+
+/// Hashes an address into `bits` bits with fibonacci hashing, `bits` must not
+/// be zero
+#[inline]
+pub(crate) fn hash(addr: usize, bits: u32) -> usize {
+    // The golden ratio as a fixed point fraction of the word size
+    const PHI: usize = if size_of::<usize>() == 8 {
+        0x9E37_79B9_7F4A_7C15_u64 as usize
+    } else {
+        0x9E37_79B9
+    };
+
+    addr.wrapping_mul(PHI) >> (usize::BITS - bits)
+}
+
+impl Clone for PointerSet {
+    fn clone(&self) -> Self {
+        Self {
+            slots: self.slots.clone(),
+            len: self.len,
+            contains_null: self.contains_null,
+        }
+    }
+
+    // Reuses the current table, used when restoring snapshots
+    fn clone_from(&mut self, source: &Self) {
+        self.slots.clone_from(&source.slots);
+        self.len = source.len;
+        self.contains_null = source.contains_null;
+    }
+}
+
+impl Debug for PointerSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+/// A snapshot of the pointers a [`Context`] has seen, see
+/// [`Context::pointer_snapshot()`]
+#[derive(Debug, Clone, Default)]
+pub struct PointerSnapshot {
+    pointers: PointerSet,
+}
+
+impl PointerSnapshot {
+    #[inline]
+    pub(crate) const fn new(pointers: PointerSet) -> Self {
+        Self { pointers }
+    }
+
+    #[inline]
+    pub(crate) const fn pointers(&self) -> &PointerSet {
+        &self.pointers
+    }
+
+    /// Returns the number of pointers within the snapshot
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.pointers.len()
+    }
+
+    /// Returns `true` if the snapshot doesn't contain any pointers
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl SizeOf for PointerSnapshot {
+    fn size_of_children(&self, context: &mut Context) {
+        self.pointers.size_of_children(context);
+    }
+}
+
+impl SizeOf for PointerSet {
+    fn size_of_children(&self, context: &mut Context) {
+        if !self.slots.is_empty() {
+            context.add_vectorlike_allocation(self.len, self.slots.len(), size_of::<usize>());
+        }
+    }
+}
@@ -4,20 +4,80 @@ use alloc::{
     rc::{Rc, Weak as RcWeak},
     sync::{Arc, Weak as ArcWeak},
 };
-use core::{mem::size_of_val, ptr::NonNull, sync::atomic::AtomicPtr};
+use core::{
+    alloc::Layout,
+    mem::size_of_val,
+    ptr::NonNull,
+    sync::atomic::{AtomicPtr, AtomicUsize},
+};
+
+/// How a [`Context`] records weak pointers (`rc::Weak` and `sync::Weak`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum WeakPolicy {
+    /// Weak pointers don't record anything, this is the default
+    #[default]
+    Ignore,
+    /// Weak pointers record their pointee as shared if it's still alive and
+    /// hasn't been seen through another pointer yet
+    ///
+    /// Weak pointers don't own their pointee, so with
+    /// [`SharedAttribution::Proportional`] they're never charged a share of it
+    SharedIfLive,
+    /// Weak pointers only record the allocation they keep alive after all strong
+    /// pointers are dropped, the pointee is dropped at that point so only the
+    /// `RcBox`/`ArcInner` allocation itself is recorded as shared
+    RetainedControlBlock,
+}
+
+/// How a [`Context`] attributes shared allocations (`Rc` and `Arc`) to the
+/// pointers that own them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SharedAttribution {
+    /// The first owner that's seen is charged the entire allocation and every
+    /// other owner is charged nothing, this is the default
+    #[default]
+    FirstOwner,
+    /// Every owner is charged an equal fraction of the allocation based on the
+    /// pointer's strong count, so the charges don't depend on traversal order
+    ///
+    /// The named nodes and types within shared allocations aren't recorded
+    /// since they're only partially charged
+    Proportional,
+}
+
+/// Returns the size of the `RcBox`/`ArcInner` allocation holding a value with
+/// the given layout, both are a strong and weak count followed by the value
+// https://github.com/rust-lang/rust/blob/1.67.0/library/alloc/src/rc.rs#L286-L291
+// https://github.com/rust-lang/rust/blob/1.67.0/library/alloc/src/sync.rs#L338-L348
+pub(crate) fn control_block_size<Count>(value: Layout) -> usize {
+    Layout::new::<[Count; 2]>()
+        .extend(value)
+        .map_or(value.size(), |(layout, _)| layout.pad_to_align().size())
+}
 
-// TODO: Do we want to traverse all *accessible* memory or all *owned* memory?
+/// Returns `true` if a weak pointer's pointer is the sentinel used by
+/// `Weak::new()`, which doesn't allocate anything
+#[inline]
+fn is_dangling<T: ?Sized>(ptr: *const T) -> bool {
+    ptr as *const u8 as usize == usize::MAX
+}
+
+// References only point to *accessible* memory and not *owned* memory, so they're
+// only traversed when the context is following references. Their data is recorded
+// as borrowed so that it doesn't get mixed up with owned memory
 impl<T> SizeOf for &T
 where
     T: SizeOf + ?Sized,
 {
     #[inline]
-    fn size_of_children(&self, _context: &mut Context) {
-        // // Only record the data behind the ref we've never seen it before
-        // if context.insert_ref(self) {
-        //     context.add(size_of_val(*self));
-        //     T::total_size_of_children(self, context);
-        // }
+    fn size_of_children(&self, context: &mut Context) {
+        // Only record the data behind the ref if we've never seen it before
+        if context.follows_references() && context.insert_ref(*self) {
+            context.borrowed(|ctx| {
+                ctx.add(size_of_val(*self));
+                T::size_of_children(self, ctx);
+            });
+        }
     }
 }
 
@@ -26,23 +86,30 @@ where
     T: SizeOf + ?Sized,
 {
     #[inline]
-    fn size_of_children(&self, _context: &mut Context) {
-        // Mutable references are exclusive so there should only ever be one of
-        // them
-        //
-        // context.add(size_of_val(*self));
-        // T::total_size_of_children(self, context);
+    fn size_of_children(&self, context: &mut Context) {
+        // Mutable references are exclusive, but the data they point to could
+        // still be reachable through a shared reference we've already seen
+        if context.follows_references() && context.insert_ref(&**self) {
+            context.borrowed(|ctx| {
+                ctx.add(size_of_val(&**self));
+                T::size_of_children(self, ctx);
+            });
+        }
     }
 }
 
 impl<T: ?Sized> SizeOf for *const T {
     #[inline]
     fn size_of_children(&self, _context: &mut Context) {}
+
+    const IS_CHILDLESS: bool = true;
 }
 
 impl<T: ?Sized> SizeOf for *mut T {
     #[inline]
     fn size_of_children(&self, _context: &mut Context) {}
+
+    const IS_CHILDLESS: bool = true;
 }
 
 impl<T> SizeOf for Box<T>
@@ -50,12 +117,14 @@ where
     T: SizeOf + ?Sized,
 {
     fn size_of_children(&self, context: &mut Context) {
-        let size = size_of_val(self.as_ref());
-        if size != 0 {
-            context.add(size).add_distinct_allocation();
-        }
+        context.attribute_to::<Self, _>(|ctx| {
+            let size = size_of_val(self.as_ref());
+            if size != 0 {
+                ctx.add(size).add_allocation(size);
+            }
 
-        T::size_of_children(self, context);
+            ctx.descend(|ctx| T::size_of_children(self, ctx));
+        });
     }
 }
 
@@ -64,22 +133,65 @@ where
     T: SizeOf + ?Sized,
 {
     fn size_of_children(&self, context: &mut Context) {
-        if context.insert_rc(self) {
-            context
-                .shared(|ctx| {
-                    ctx.add(size_of_val(self.as_ref()));
-                    T::size_of_children(self, ctx);
-                })
-                .add_distinct_allocation();
-        }
+        size_of_rc::<Self, _>(self, Rc::strong_count(self), context);
     }
 }
 
-// Weak refs aren't owned
-// TODO: Should we record the data pointed to by weak refs as shared?
-impl<T: ?Sized> SizeOf for RcWeak<T> {
-    #[inline]
-    fn size_of_children(&self, _context: &mut Context) {}
+/// Records the allocation of `rc` as shared by `owners` pointers of type `P`
+fn size_of_rc<P, T>(rc: &Rc<T>, owners: usize, context: &mut Context)
+where
+    P: ?Sized,
+    T: SizeOf + ?Sized,
+{
+    context.add_shared_allocation::<P, _, _>(Rc::as_ptr(rc), owners, |ctx| {
+        // Record the entire `RcBox`, including its counts and padding
+        let size = control_block_size::<usize>(Layout::for_value(rc.as_ref()));
+        ctx.shared(|ctx| {
+            ctx.add(size).descend(|ctx| T::size_of_children(rc, ctx));
+        })
+        .add_allocation(size);
+    });
+}
+
+// Weak refs aren't owned, so they only record anything when the context's
+// `WeakPolicy` asks them to. Their pointee may already be dropped, so only
+// sized pointees are supported since their layout can't be read from a
+// dropped value
+impl<T> SizeOf for RcWeak<T>
+where
+    T: SizeOf,
+{
+    fn size_of_children(&self, context: &mut Context) {
+        match context.weak_policy() {
+            WeakPolicy::Ignore => {}
+
+            // Only the strong pointers are charged for the pointee
+            WeakPolicy::SharedIfLive
+                if context.shared_attribution() == SharedAttribution::Proportional => {}
+
+            WeakPolicy::SharedIfLive => {
+                if let Some(rc) = self.upgrade() {
+                    // Upgrading adds a strong pointer that doesn't own the allocation
+                    let owners = Rc::strong_count(&rc) - 1;
+                    size_of_rc::<Self, _>(&rc, owners, context);
+                }
+            }
+
+            WeakPolicy::RetainedControlBlock => {
+                let ptr = self.as_ptr();
+                if self.strong_count() == 0 && !is_dangling(ptr) && context.insert_ptr(ptr) {
+                    let size = control_block_size::<usize>(Layout::new::<T>());
+
+                    context.attribute_to::<Self, _>(|ctx| {
+                        ctx.shared(|ctx| {
+                            ctx.add(size);
+                        })
+                        .add_allocation(size);
+                    });
+                }
+            }
+        }
+    }
 }
 
 impl<T> SizeOf for Arc<T>
@@ -87,27 +199,71 @@ where
     T: SizeOf + ?Sized,
 {
     fn size_of_children(&self, context: &mut Context) {
-        if context.insert_arc(self) {
-            context
-                .shared(|ctx| {
-                    ctx.add(size_of_val(self.as_ref()));
-                    T::size_of_children(self, ctx);
-                })
-                .add_distinct_allocation();
-        }
+        size_of_arc::<Self, _>(self, Arc::strong_count(self), context);
     }
 }
 
-// Weak refs aren't owned
-// TODO: Should we record the data pointed to by weak refs as shared?
-impl<T: ?Sized> SizeOf for ArcWeak<T> {
-    #[inline]
-    fn size_of_children(&self, _context: &mut Context) {}
+/// Records the allocation of `arc` as shared by `owners` pointers of type `P`
+fn size_of_arc<P, T>(arc: &Arc<T>, owners: usize, context: &mut Context)
+where
+    P: ?Sized,
+    T: SizeOf + ?Sized,
+{
+    context.add_shared_allocation::<P, _, _>(Arc::as_ptr(arc), owners, |ctx| {
+        // Record the entire `ArcInner`, including its counts and padding
+        let size = control_block_size::<AtomicUsize>(Layout::for_value(arc.as_ref()));
+        ctx.shared(|ctx| {
+            ctx.add(size).descend(|ctx| T::size_of_children(arc, ctx));
+        })
+        .add_allocation(size);
+    });
+}
+
+// Weak refs aren't owned, so they only record anything when the context's
+// `WeakPolicy` asks them to, see the `RcWeak` impl
+impl<T> SizeOf for ArcWeak<T>
+where
+    T: SizeOf,
+{
+    fn size_of_children(&self, context: &mut Context) {
+        match context.weak_policy() {
+            WeakPolicy::Ignore => {}
+
+            // Only the strong pointers are charged for the pointee
+            WeakPolicy::SharedIfLive
+                if context.shared_attribution() == SharedAttribution::Proportional => {}
+
+            WeakPolicy::SharedIfLive => {
+                if let Some(arc) = self.upgrade() {
+                    // Upgrading adds a strong pointer that doesn't own the
+                    // allocation, other threads can still change the count
+                    let owners = Arc::strong_count(&arc).saturating_sub(1);
+                    size_of_arc::<Self, _>(&arc, owners, context);
+                }
+            }
+
+            WeakPolicy::RetainedControlBlock => {
+                let ptr = self.as_ptr();
+                if self.strong_count() == 0 && !is_dangling(ptr) && context.insert_ptr(ptr) {
+                    let size = control_block_size::<AtomicUsize>(Layout::new::<T>());
+
+                    context.attribute_to::<Self, _>(|ctx| {
+                        ctx.shared(|ctx| {
+                            ctx.add(size);
+                        })
+                        .add_allocation(size);
+                    });
+                }
+            }
+        }
+    }
 }
 
 impl<T: ?Sized> SizeOf for NonNull<T> {
     #[inline]
     fn size_of_children(&self, _context: &mut Context) {}
+
+    const IS_CHILDLESS: bool = true;
 }
 
 impl_total_size_childless! {
@@ -0,0 +1,435 @@
+use crate::{Context, HumanBytes, SizeOf, TotalSize};
+use alloc::{borrow::Cow, string::String, vec::Vec};
+use core::{
+    cmp::Ordering,
+    fmt::{self, Display, Write},
+};
+
+/// A hierarchical breakdown of the size of a value
+///
+/// Created by [`SizeOf::size_of_tree()`] or [`Context::size_report()`], each
+/// node of the report is a field, variant or collection that was recorded
+/// while measuring the value
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SizeReport {
+    root: SizeNode,
+}
+
+impl SizeReport {
+    /// Creates a new report from its root node
+    #[inline]
+    pub const fn new(root: SizeNode) -> Self {
+        Self { root }
+    }
+
+    /// Returns the root node of the report
+    #[inline]
+    pub const fn root(&self) -> &SizeNode {
+        &self.root
+    }
+
+    /// Returns the total size of the measured value
+    #[inline]
+    pub const fn total_size(&self) -> TotalSize {
+        self.root.size
+    }
+
+    /// Walks every node in the report in depth-first order, yielding each node
+    /// along with its depth (the root has a depth of zero)
+    #[inline]
+    pub fn iter(&self) -> SizeReportIter<'_> {
+        self.root.iter()
+    }
+
+    /// Sorts all nodes of the report so that the largest children (by total
+    /// bytes) come first
+    #[inline]
+    pub fn sort_by_total_bytes(&mut self) -> &mut Self {
+        self.root.sort_by_total_bytes();
+        self
+    }
+
+    /// Sorts the children of every node within the report with the given
+    /// comparison function
+    #[inline]
+    pub fn sort_by<F>(&mut self, mut compare: F) -> &mut Self
+    where
+        F: FnMut(&SizeNode, &SizeNode) -> Ordering,
+    {
+        self.root.sort_by_inner(&mut compare);
+        self
+    }
+
+    /// Consumes the report, returning its root node
+    #[inline]
+    pub fn into_root(self) -> SizeNode {
+        self.root
+    }
+
+    /// Writes the report in the collapsed stack format read by flamegraph
+    /// tools like [`inferno`](https://docs.rs/inferno) and `flamegraph.pl`
+    ///
+    /// Every node is written as a line of its path from the root separated by
+    /// `;` followed by its own `metric` bytes, which don't include the bytes of
+    /// its children. Nodes without any bytes of their own are skipped and `;`s
+    /// within names are replaced with `:`s
+    ///
+    /// ```rust
+    /// use size_of::{ByteMetric, Context, SizeOf};
+    ///
+    /// struct Service {
+    ///     name: String,
+    ///     cache: Vec<u64>,
+    /// }
+    ///
+    /// // `#[derive(SizeOf)]` names a node after every field in the same way
+    /// impl SizeOf for Service {
+    ///     fn size_of_children(&self, context: &mut Context) {
+    ///         context
+    ///             .named("name", |ctx| self.name.size_of_children(ctx))
+    ///             .named("cache", |ctx| self.cache.size_of_children(ctx));
+    ///     }
+    /// }
+    ///
+    /// let service = Service {
+    ///     name: String::from("service"),
+    ///     cache: vec![0; 1024],
+    /// };
+    ///
+    /// let mut context = Context::new();
+    /// context.record_tree();
+    /// service.size_of_with_context(&mut context);
+    ///
+    /// let folded = context.size_report("Service").to_folded(ByteMetric::Total);
+    /// assert!(folded.contains("Service;cache;Vec 8192\n"));
+    /// assert!(folded.contains("Service;name 7\n"));
+    /// ```
+    pub fn write_folded<W>(&self, writer: &mut W, metric: ByteMetric) -> fmt::Result
+    where
+        W: Write,
+    {
+        self.root.write_folded(writer, metric, &mut String::new())
+    }
+
+    /// Returns the report in the collapsed stack format, see
+    /// [`SizeReport::write_folded()`]
+    pub fn to_folded(&self, metric: ByteMetric) -> String {
+        let mut folded = String::new();
+        self.write_folded(&mut folded, metric)
+            .expect("writing to a string can't fail");
+
+        folded
+    }
+}
+
+impl Display for SizeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (depth, node) in self.iter() {
+            let size = node.size();
+            writeln!(
+                f,
+                "{:indent$}{}: {} total, {} excess, {} shared, {} allocations",
+                "",
+                node.name(),
+                HumanBytes::from(size.total_bytes()),
+                HumanBytes::from(size.excess_bytes()),
+                HumanBytes::from(size.shared_bytes()),
+                size.distinct_allocations(),
+                indent = depth * 2,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl SizeOf for SizeReport {
+    fn size_of_children(&self, context: &mut Context) {
+        self.root.size_of_children(context);
+    }
+}
+
+/// A single node within a [`SizeReport`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SizeNode {
+    /// The name of the node, e.g. the name of a field
+    name: Cow<'static, str>,
+    /// The total size of everything recorded under the node
+    size: TotalSize,
+    /// The child nodes of the current node
+    children: Vec<SizeNode>,
+}
+
+impl SizeNode {
+    /// Creates a new node
+    #[inline]
+    pub fn new<N>(name: N, size: TotalSize, children: Vec<SizeNode>) -> Self
+    where
+        N: Into<Cow<'static, str>>,
+    {
+        Self {
+            name: name.into(),
+            size,
+            children,
+        }
+    }
+
+    /// Returns the name of the node
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the total size of everything recorded under the node,
+    /// including all of its children
+    #[inline]
+    pub const fn size(&self) -> TotalSize {
+        self.size
+    }
+
+    /// Returns the children of the node
+    #[inline]
+    pub fn children(&self) -> &[SizeNode] {
+        &self.children
+    }
+
+    /// Returns the child with the given name (if it exists)
+    #[inline]
+    pub fn child(&self, name: &str) -> Option<&SizeNode> {
+        self.children.iter().find(|child| child.name() == name)
+    }
+
+    /// Returns `true` if the node has no children
+    #[inline]
+    pub fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    /// Walks the current node and all of its children in depth-first order,
+    /// yielding each node along with its depth relative to the current one
+    #[inline]
+    pub fn iter(&self) -> SizeReportIter<'_> {
+        SizeReportIter {
+            stack: alloc::vec![(0, self)],
+        }
+    }
+
+    /// Sorts the children of the current node and all of its children so that
+    /// the largest nodes (by total bytes) come first
+    #[inline]
+    pub fn sort_by_total_bytes(&mut self) -> &mut Self {
+        self.sort_by_inner(&mut |a: &SizeNode, b: &SizeNode| {
+            b.size.total_bytes().cmp(&a.size.total_bytes())
+        });
+        self
+    }
+
+    /// Writes the current node and all of its children in the collapsed stack
+    /// format, `path` holds the path of the current node's parent
+    fn write_folded<W>(&self, writer: &mut W, metric: ByteMetric, path: &mut String) -> fmt::Result
+    where
+        W: Write,
+    {
+        let parent = path.len();
+        if !path.is_empty() {
+            path.push(';');
+        }
+        path.extend(self.name.chars().map(|char| match char {
+            ';' => ':',
+            '\n' | '\r' => ' ',
+            char => char,
+        }));
+
+        let children = self.children.iter().fold(0usize, |bytes, child| {
+            bytes.saturating_add(metric.of(child.size))
+        });
+        let own = metric.of(self.size).saturating_sub(children);
+        if own != 0 {
+            writeln!(writer, "{path} {own}")?;
+        }
+
+        for child in &self.children {
+            child.write_folded(writer, metric, path)?;
+        }
+        path.truncate(parent);
+
+        Ok(())
+    }
+
+    fn sort_by_inner<F>(&mut self, compare: &mut F)
+    where
+        F: FnMut(&SizeNode, &SizeNode) -> Ordering,
+    {
+        self.children.sort_by(|a, b| compare(a, b));
+        for child in &mut self.children {
+            child.sort_by_inner(compare);
+        }
+    }
+}
+
+impl SizeOf for SizeNode {
+    fn size_of_children(&self, context: &mut Context) {
+        self.name.size_of_children(context);
+        self.children.size_of_children(context);
+    }
+}
+
+/// The bytes of a [`TotalSize`] that are exported, see
+/// [`SizeReport::write_folded()`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ByteMetric {
+    /// The total bytes, see [`TotalSize::total_bytes()`]
+    #[default]
+    Total,
+    /// The used bytes, see [`TotalSize::used_bytes()`]
+    Used,
+    /// The excess bytes, see [`TotalSize::excess_bytes()`]
+    Excess,
+}
+
+impl ByteMetric {
+    /// Returns the bytes of `size` this metric measures
+    #[inline]
+    pub const fn of(self, size: TotalSize) -> usize {
+        match self {
+            Self::Total => size.total_bytes(),
+            Self::Used => size.used_bytes(),
+            Self::Excess => size.excess_bytes(),
+        }
+    }
+}
+
+/// A depth-first iterator over the nodes of a [`SizeReport`]
+#[derive(Debug, Clone)]
+pub struct SizeReportIter<'a> {
+    stack: Vec<(usize, &'a SizeNode)>,
+}
+
+impl<'a> Iterator for SizeReportIter<'a> {
+    type Item = (usize, &'a SizeNode);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let (depth, node) = self.stack.pop()?;
+        self.stack
+            .extend(node.children.iter().rev().map(|child| (depth + 1, child)));
+
+        Some((depth, node))
+    }
+}
+
+/// Records the tree of named nodes while a [`Context`] is traversing a value
+#[derive(Debug, Clone)]
+pub(crate) struct TreeBuilder {
+    /// All nodes within the tree, the root is always at index zero
+    nodes: Vec<BuilderNode>,
+    /// The currently entered nodes along with the context's total size at
+    /// the time they were entered
+    stack: Vec<(usize, TotalSize)>,
+}
+
+#[derive(Debug, Clone)]
+struct BuilderNode {
+    name: &'static str,
+    size: TotalSize,
+    children: Vec<usize>,
+}
+
+impl TreeBuilder {
+    pub(crate) fn new() -> Self {
+        Self {
+            nodes: alloc::vec![BuilderNode {
+                name: "",
+                size: TotalSize::zero(),
+                children: Vec::new(),
+            }],
+            stack: Vec::new(),
+        }
+    }
+
+    /// Removes every recorded node while keeping the allocated nodes
+    pub(crate) fn clear(&mut self) {
+        self.nodes.truncate(1);
+        self.nodes[0].children.clear();
+        self.stack.clear();
+    }
+
+    /// Enters the child of the current node named `name`, creating it if it
+    /// doesn't exist yet. Entering the same name multiple times aggregates
+    /// everything recorded under it into a single node
+    pub(crate) fn enter(&mut self, name: &'static str, current: TotalSize) {
+        let parent = self.current();
+        let node = self.child(parent, name);
+        self.stack.push((node, current));
+    }
+
+    /// Merges every node recorded by `other` into the current node
+    pub(crate) fn merge(&mut self, other: &Self) {
+        let parent = self.current();
+        self.merge_children(parent, other, 0);
+    }
+
+    fn merge_children(&mut self, parent: usize, other: &Self, other_parent: usize) {
+        for &other_child in &other.nodes[other_parent].children {
+            let other_node = &other.nodes[other_child];
+            let node = self.child(parent, other_node.name);
+            self.nodes[node].size += other_node.size;
+            self.merge_children(node, other, other_child);
+        }
+    }
+
+    /// Returns the currently entered node
+    fn current(&self) -> usize {
+        self.stack.last().map_or(0, |&(node, _)| node)
+    }
+
+    /// Returns the child of `parent` named `name`, creating it if it doesn't
+    /// exist yet
+    fn child(&mut self, parent: usize, name: &'static str) -> usize {
+        let existing = self.nodes[parent]
+            .children
+            .iter()
+            .copied()
+            .find(|&child| self.nodes[child].name == name);
+
+        existing.unwrap_or_else(|| {
+            let node = self.nodes.len();
+            self.nodes.push(BuilderNode {
+                name,
+                size: TotalSize::zero(),
+                children: Vec::new(),
+            });
+            self.nodes[parent].children.push(node);
+
+            node
+        })
+    }
+
+    /// Exits the current node, attributing everything recorded since it was
+    /// entered to it
+    pub(crate) fn exit(&mut self, current: TotalSize) {
+        if let Some((node, start)) = self.stack.pop() {
+            self.nodes[node].size += current - start;
+        }
+    }
+
+    /// Builds a report from the recorded tree, nodes that nothing was
+    /// recorded under are omitted
+    pub(crate) fn build(&self, name: Cow<'static, str>, total: TotalSize) -> SizeReport {
+        SizeReport::new(SizeNode::new(name, total, self.build_children(0)))
+    }
+
+    fn build_children(&self, node: usize) -> Vec<SizeNode> {
+        self.nodes[node]
+            .children
+            .iter()
+            .filter(|&&child| self.nodes[child].size != TotalSize::zero())
+            .map(|&child| {
+                let node = &self.nodes[child];
+                SizeNode::new(node.name, node.size, self.build_children(child))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,231 @@
+use crate::{Context, DiffReport, DynSizeOf, HumanBytes, SizeOf, TotalSize};
+use alloc::{borrow::Cow, collections::BTreeMap, string::ToString, vec::Vec};
+use core::{
+    fmt::{self, Display},
+    slice,
+};
+
+/// Measures each of the given named roots and splits their memory into the
+/// bytes only they keep alive and the bytes they share with other roots
+///
+/// This is similar to the retained sizes of a heap dominator analysis, the
+/// exclusive bytes of a root would be freed if it was dropped while its shared
+/// bytes would be kept alive by the other roots. Memory is shared through `Rc`
+/// and `Arc`-type pointers, see [`RetainedSize`]
+///
+/// ```rust
+/// use size_of::DynSizeOf;
+/// use std::sync::Arc;
+///
+/// let interned = Arc::new(String::from("some interned string"));
+/// let first = vec![interned.clone(), Arc::new(String::from("only in first"))];
+/// let second = vec![interned];
+///
+/// let report = size_of::retained_sizes([
+///     ("first", &first as &dyn DynSizeOf),
+///     ("second", &second as &dyn DynSizeOf),
+/// ]);
+///
+/// let first = report.get("first").unwrap();
+/// let second = report.get("second").unwrap();
+/// assert_eq!(first.shared(), second.shared());
+/// assert!(first.exclusive().total_bytes() > second.exclusive().total_bytes());
+/// ```
+pub fn retained_sizes<'a, I, N>(roots: I) -> RetainedReport
+where
+    I: IntoIterator<Item = (N, &'a dyn DynSizeOf)>,
+    N: Into<Cow<'static, str>>,
+{
+    // Measure each root on its own, keeping track of the shared allocations
+    // each of them can reach
+    let measured: Vec<_> = roots
+        .into_iter()
+        .map(|(name, root)| {
+            let mut context = Context::new();
+            context.record_shared_allocations();
+            root.dyn_size_of_with_context(&mut context);
+
+            let allocations: Vec<_> = context.shared_allocations().collect();
+            (name.into(), context.total_size(), allocations)
+        })
+        .collect();
+
+    // Count the number of roots that can reach each shared allocation
+    let mut reachable_from = BTreeMap::new();
+    for (_, _, allocations) in &measured {
+        for &(addr, _) in allocations {
+            *reachable_from.entry(addr).or_insert(0usize) += 1;
+        }
+    }
+
+    let roots = measured
+        .into_iter()
+        .map(|(name, total, allocations)| {
+            let shared = allocations
+                .iter()
+                .filter(|(addr, _)| reachable_from[addr] > 1)
+                .map(|&(_, size)| size)
+                .sum();
+
+            RetainedSize::new(name, total - shared, shared)
+        })
+        .collect();
+
+    RetainedReport::new(roots)
+}
+
+/// The retained sizes of a set of roots, created by [`retained_sizes()`]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RetainedReport {
+    roots: Vec<RetainedSize>,
+}
+
+impl RetainedReport {
+    /// Creates a new report from the given roots
+    #[inline]
+    pub const fn new(roots: Vec<RetainedSize>) -> Self {
+        Self { roots }
+    }
+
+    /// Returns the number of roots within the report
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.roots.len()
+    }
+
+    /// Returns `true` if the report contains no roots
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.roots.is_empty()
+    }
+
+    /// Returns the root with the given name (if it exists)
+    #[inline]
+    pub fn get(&self, name: &str) -> Option<&RetainedSize> {
+        self.roots.iter().find(|root| root.name() == name)
+    }
+
+    /// Returns an iterator over all roots within the report in the order they
+    /// were given
+    #[inline]
+    pub fn iter(&self) -> slice::Iter<'_, RetainedSize> {
+        self.roots.iter()
+    }
+
+    /// Compares the total size of every root within the current report to
+    /// the same root within `after`, e.g. to find the roots that grew while
+    /// running a workload
+    pub fn diff(&self, after: &Self) -> DiffReport {
+        let sizes = |report: &Self| -> Vec<_> {
+            report
+                .roots
+                .iter()
+                .map(|root| (root.name.clone(), root.total()))
+                .collect()
+        };
+
+        DiffReport::new(sizes(self), sizes(after))
+    }
+}
+
+impl<'a> IntoIterator for &'a RetainedReport {
+    type Item = &'a RetainedSize;
+    type IntoIter = slice::Iter<'a, RetainedSize>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl Display for RetainedReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>12} {:>12} {:>12}  root",
+            "exclusive", "shared", "total"
+        )?;
+
+        for root in &self.roots {
+            writeln!(
+                f,
+                "{:>12} {:>12} {:>12}  {}",
+                HumanBytes::from(root.exclusive.total_bytes()).to_string(),
+                HumanBytes::from(root.shared.total_bytes()).to_string(),
+                HumanBytes::from(root.total().total_bytes()).to_string(),
+                root.name,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl SizeOf for RetainedReport {
+    fn size_of_children(&self, context: &mut Context) {
+        self.roots.size_of_children(context);
+    }
+}
+
+/// The memory kept alive by a single root within a [`RetainedReport`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RetainedSize {
+    name: Cow<'static, str>,
+    /// The memory only this root keeps alive
+    exclusive: TotalSize,
+    /// The memory this root shares with other roots
+    shared: TotalSize,
+}
+
+impl RetainedSize {
+    /// Creates a new `RetainedSize`
+    #[inline]
+    pub fn new<N>(name: N, exclusive: TotalSize, shared: TotalSize) -> Self
+    where
+        N: Into<Cow<'static, str>>,
+    {
+        Self {
+            name: name.into(),
+            exclusive,
+            shared,
+        }
+    }
+
+    /// Returns the name of the root
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the memory only this root keeps alive, this would be freed if
+    /// the root was dropped
+    ///
+    /// Shared allocations that are only reachable from this root are
+    /// exclusive, even if they're also owned by something outside of the
+    /// measured roots
+    #[inline]
+    pub const fn exclusive(&self) -> TotalSize {
+        self.exclusive
+    }
+
+    /// Returns the memory this root shares with at least one other root, this
+    /// would be kept alive by the other roots if the root was dropped
+    #[inline]
+    pub const fn shared(&self) -> TotalSize {
+        self.shared
+    }
+
+    /// Returns all memory reachable from the root
+    #[inline]
+    pub fn total(&self) -> TotalSize {
+        self.exclusive + self.shared
+    }
+}
+
+impl SizeOf for RetainedSize {
+    fn size_of_children(&self, context: &mut Context) {
+        self.name.size_of_children(context);
+    }
+}
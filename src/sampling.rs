@@ -0,0 +1,219 @@
+use crate::{Context, SizeOf};
+
+/// The z-score of a 95% confidence interval
+const Z_95: f64 = 1.96;
+
+/// Describes how much of a [`Context`]'s total size was estimated by sampling
+/// collections, see [`Context::set_sample_size()`]
+///
+/// The children of a sampled collection are extrapolated from the children of
+/// the sampled elements, so the error of the estimate depends on how much the
+/// children of each element differ in size
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct SamplingReport {
+    /// The total bytes of the context
+    total_bytes: usize,
+    /// The total bytes that were extrapolated instead of measured
+    estimated_bytes: usize,
+    /// The number of collections that were sampled
+    sampled_collections: usize,
+    /// The number of elements whose children were measured within the sampled
+    /// collections
+    sampled_elements: usize,
+    /// The total number of elements within the sampled collections
+    total_elements: usize,
+    /// The variance of the estimated bytes, this is always finite
+    variance: f64,
+}
+
+impl SamplingReport {
+    /// Returns `true` if any collections were sampled, when this is false the
+    /// context's total size is exact
+    #[inline]
+    pub const fn is_estimate(&self) -> bool {
+        self.sampled_collections != 0
+    }
+
+    /// Returns the total bytes of the context, including estimated bytes
+    #[inline]
+    pub const fn total_bytes(&self) -> usize {
+        self.total_bytes
+    }
+
+    /// Returns the number of bytes that were extrapolated instead of measured
+    #[inline]
+    pub const fn estimated_bytes(&self) -> usize {
+        self.estimated_bytes
+    }
+
+    /// Returns the number of collections that were sampled
+    #[inline]
+    pub const fn sampled_collections(&self) -> usize {
+        self.sampled_collections
+    }
+
+    /// Returns the number of elements whose children were measured within
+    /// the sampled collections
+    #[inline]
+    pub const fn sampled_elements(&self) -> usize {
+        self.sampled_elements
+    }
+
+    /// Returns the total number of elements within the sampled collections
+    #[inline]
+    pub const fn total_elements(&self) -> usize {
+        self.total_elements
+    }
+
+    /// Returns the standard error of the total bytes
+    #[inline]
+    pub fn standard_error(&self) -> f64 {
+        sqrt(self.variance)
+    }
+
+    /// Returns the lower and upper bounds of the total bytes with the given
+    /// z-score, e.g. `1.96` for a 95% confidence interval
+    ///
+    /// The lower bound is never less than the number of bytes that were
+    /// actually measured
+    pub fn confidence_interval(&self, z_score: f64) -> (usize, usize) {
+        let margin = self.standard_error() * z_score;
+        let measured = self.total_bytes.saturating_sub(self.estimated_bytes);
+
+        let lower = (self.total_bytes as f64 - margin).max(measured as f64);
+        let upper = self.total_bytes as f64 + margin;
+        (lower as usize, upper as usize)
+    }
+
+    /// Returns the lower and upper bounds of the total bytes with 95%
+    /// confidence, see [`SamplingReport::confidence_interval()`]
+    #[inline]
+    pub fn confidence_interval_95(&self) -> (usize, usize) {
+        self.confidence_interval(Z_95)
+    }
+}
+
+impl SizeOf for SamplingReport {
+    #[inline]
+    fn size_of_children(&self, _context: &mut Context) {}
+
+    const IS_CHILDLESS: bool = true;
+}
+
+/// Aggregates the sampling done by a [`Context`]
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct SamplingStats {
+    estimated_bytes: usize,
+    sampled_collections: usize,
+    sampled_elements: usize,
+    total_elements: usize,
+    variance: f64,
+    /// The state of the random number generator used to select elements
+    rng: u64,
+}
+
+impl SamplingStats {
+    /// Returns `true` if the current element should be sampled when `needed`
+    /// more elements have to be selected from `remaining` elements
+    #[inline]
+    pub(crate) fn select(&mut self, remaining: usize, needed: usize) -> bool {
+        (self.next_random() % remaining as u64) < needed as u64
+    }
+
+    /// Generates a random number with splitmix64
+    // https://prng.di.unimi.it/splitmix64.c
+    #[inline]
+    fn next_random(&mut self) -> u64 {
+        self.rng = self.rng.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Records a sampled collection of `total` elements where `sampled`
+    /// elements were measured, `sum` and `sum_squares` are the sum and the sum
+    /// of squares of the total bytes of each sampled element's children.
+    /// `estimated` is the number of bytes that were extrapolated
+    pub(crate) fn record(
+        &mut self,
+        total: usize,
+        sampled: usize,
+        sum: f64,
+        sum_squares: f64,
+        estimated: usize,
+    ) {
+        self.estimated_bytes = self.estimated_bytes.saturating_add(estimated);
+        self.sampled_collections += 1;
+        self.sampled_elements += sampled;
+        self.total_elements += total;
+
+        if sampled > 1 {
+            // The variance of the estimated total with the finite population
+            // correction, since elements are sampled without replacement
+            let (total, sampled) = (total as f64, sampled as f64);
+            let mean = sum / sampled;
+            let sample_variance =
+                ((sum_squares - sampled * mean * mean) / (sampled - 1.0)).max(0.0);
+            self.variance = finite(
+                self.variance
+                    + total * total * (sample_variance / sampled) * (1.0 - sampled / total),
+            );
+        }
+    }
+
+    /// Merges the sampling done by another context into the current stats
+    pub(crate) fn merge(&mut self, other: &Self) {
+        self.estimated_bytes = self.estimated_bytes.saturating_add(other.estimated_bytes);
+        self.sampled_collections += other.sampled_collections;
+        self.sampled_elements += other.sampled_elements;
+        self.total_elements += other.total_elements;
+        self.variance = finite(self.variance + other.variance);
+    }
+
+    pub(crate) const fn report(&self, total_bytes: usize) -> SamplingReport {
+        SamplingReport {
+            total_bytes,
+            estimated_bytes: self.estimated_bytes,
+            sampled_collections: self.sampled_collections,
+            sampled_elements: self.sampled_elements,
+            total_elements: self.total_elements,
+            variance: self.variance,
+        }
+    }
+}
+
+/// Clamps a variance that overflowed to the largest finite one, so the
+/// variance of a [`SamplingReport`] can always be serialized
+#[inline]
+fn finite(variance: f64) -> f64 {
+    if variance.is_finite() {
+        variance
+    } else {
+        f64::MAX
+    }
+}
+
+/// Calculates the square root of `value` with newton's method since
+/// `f64::sqrt()` isn't available without std
+pub(crate) fn sqrt(value: f64) -> f64 {
+    if value <= 0.0 || value.is_nan() {
+        return 0.0;
+    } else if value.is_infinite() {
+        return value;
+    }
+
+    // Starting above the root makes every iteration decrease until it converges
+    let mut root = value.max(1.0);
+    for _ in 0..128 {
+        let next = (root + value / root) / 2.0;
+        if next >= root {
+            break;
+        }
+        root = next;
+    }
+
+    root
+}
@@ -0,0 +1,110 @@
+use crate::TotalSize;
+use alloc::{boxed::Box, vec::Vec};
+use core::{any::Any, fmt::Debug};
+
+/// Receives everything a [`Context`](crate::Context) records, see
+/// [`Context::add_sink()`](crate::Context::add_sink)
+///
+/// Sinks see the same events the context itself counts, so they can collect
+/// anything (histograms, tracing spans, custom reports) from the existing
+/// [`SizeOf`](crate::SizeOf) impls. A [`TotalSize`] is the sink that counts
+/// everything it sees, which is what every context does by default
+pub trait SizeSink: Debug + Send + Any {
+    /// Records `size` being added to the context
+    ///
+    /// Every addition is reported as the difference it makes to the
+    /// context's [`TotalSize`], so adding 16 shared bytes reports a size with
+    /// 16 total and 16 shared bytes
+    fn record(&mut self, size: TotalSize);
+
+    /// Enters a named node, see [`Context::named()`](crate::Context::named)
+    #[inline]
+    fn enter_node(&mut self, _name: &'static str) {}
+
+    /// Exits the current named node
+    #[inline]
+    fn exit_node(&mut self) {}
+
+    /// Enters a value of the type named `type_name`, see
+    /// [`Context::attribute_to()`](crate::Context::attribute_to)
+    #[inline]
+    fn enter_type(&mut self, _type_name: &'static str) {}
+
+    /// Exits the current type
+    #[inline]
+    fn exit_type(&mut self) {}
+
+    /// Enters the shared allocation at `addr` owned by an `Rc` or `Arc`, this
+    /// happens every time the allocation is seen even though it's only
+    /// measured once, see [`SharedAttribution`](crate::SharedAttribution)
+    #[inline]
+    fn enter_shared(&mut self, _addr: usize) {}
+
+    /// Exits the current shared allocation
+    #[inline]
+    fn exit_shared(&mut self) {}
+}
+
+impl SizeSink for TotalSize {
+    #[inline]
+    fn record(&mut self, size: TotalSize) {
+        *self += size;
+    }
+}
+
+/// The sinks of a [`Context`](crate::Context)
+///
+/// Sinks can't be cloned, so cloning a context leaves the clone without any
+#[derive(Debug, Default)]
+pub(crate) struct Sinks(Vec<Box<dyn SizeSink>>);
+
+impl Sinks {
+    #[inline]
+    pub(crate) fn push(&mut self, sink: Box<dyn SizeSink>) {
+        self.0.push(sink);
+    }
+
+    /// Returns the first sink of type `S`
+    pub(crate) fn get<S: SizeSink>(&self) -> Option<&S> {
+        self.0
+            .iter()
+            .find_map(|sink| (&**sink as &dyn Any).downcast_ref::<S>())
+    }
+
+    /// Returns the first sink of type `S`
+    pub(crate) fn get_mut<S: SizeSink>(&mut self) -> Option<&mut S> {
+        self.0
+            .iter_mut()
+            .find_map(|sink| (&mut **sink as &mut dyn Any).downcast_mut::<S>())
+    }
+
+    /// Removes the first sink of type `S`
+    pub(crate) fn remove<S: SizeSink>(&mut self) -> Option<S> {
+        let idx = self
+            .0
+            .iter()
+            .position(|sink| (&**sink as &dyn Any).is::<S>())?;
+        let sink: Box<dyn Any> = self.0.remove(idx);
+        sink.downcast().ok().map(|sink| *sink)
+    }
+
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    #[inline]
+    pub(crate) fn for_each<F>(&mut self, with_sink: F)
+    where
+        F: FnMut(&mut Box<dyn SizeSink>),
+    {
+        self.0.iter_mut().for_each(with_sink);
+    }
+}
+
+impl Clone for Sinks {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
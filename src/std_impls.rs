@@ -1,6 +1,6 @@
 #![cfg(feature = "std")]
 
-use crate::{Context, SizeOf};
+use crate::{Context, LockPolicy, SizeOf};
 use core::{
     cell::{Cell, UnsafeCell},
     mem::size_of,
@@ -30,6 +30,9 @@ const PATH_ELEM_SIZE: usize = if cfg!(windows) {
 impl_total_size_childless! {
     Path,
     OsStr,
+}
+
+impl_total_size_childless! {
     Barrier,
     Condvar,
     Instant,
@@ -53,9 +56,9 @@ impl_total_size_childless! {
 impl SizeOf for OsString {
     fn size_of_children(&self, context: &mut Context) {
         if self.capacity() != 0 {
-            context
-                .add_vectorlike(self.len(), self.capacity(), PATH_ELEM_SIZE)
-                .add_distinct_allocation();
+            context.attribute_to::<Self, _>(|ctx| {
+                ctx.add_vectorlike_allocation(self.len(), self.capacity(), PATH_ELEM_SIZE);
+            });
         }
     }
 }
@@ -63,9 +66,13 @@ impl SizeOf for OsString {
 impl SizeOf for PathBuf {
     fn size_of_children(&self, context: &mut Context) {
         if self.capacity() != 0 {
-            context
-                .add_vectorlike(self.as_os_str().len(), self.capacity(), PATH_ELEM_SIZE)
-                .add_distinct_allocation();
+            context.attribute_to::<Self, _>(|ctx| {
+                ctx.add_vectorlike_allocation(
+                    self.as_os_str().len(),
+                    self.capacity(),
+                    PATH_ELEM_SIZE,
+                );
+            });
         }
     }
 }
@@ -77,17 +84,28 @@ where
     // TODO: More target-specific size estimation
     fn size_of_children(&self, context: &mut Context) {
         if cfg!(target_env = "sgx") {
-            context
-                .add(estimate_mutex_size_sgx::<T>())
-                .add_distinct_allocation();
+            let size = estimate_mutex_size_sgx::<T>();
+            context.attribute_to::<Self, _>(|ctx| {
+                ctx.estimated(|ctx| {
+                    ctx.add(size).add_allocation(size);
+                });
+            });
         }
 
         // TODO: hermit does allocate a priority queue but we have no way to know how
         // big it is https://github.com/rust-lang/rust/blob/98f3001eecbe4cbd091c10ffab45b4c164bb507b/library/std/src/sys/hermit/mutex.rs#L95-L98
 
-        // Ignore any errors that occur while trying to lock a Mutex
-        if let Ok(contents) = self.lock() {
-            contents.size_of_children(context);
+        // Poisoned mutexes and ones we can't lock without blocking are unmeasured
+        let contents = match context.lock_policy() {
+            LockPolicy::Block => self.lock().ok(),
+            LockPolicy::TryLock => self.try_lock().ok(),
+        };
+
+        match contents {
+            Some(contents) => contents.size_of_children(context),
+            None => {
+                context.add_unmeasured();
+            }
         }
     }
 }
@@ -158,9 +176,17 @@ where
     T: SizeOf,
 {
     fn size_of_children(&self, context: &mut Context) {
-        // Ignore any errors that occur while trying to lock an RwLock
-        if let Ok(contents) = self.read() {
-            contents.size_of_children(context);
+        // Poisoned locks and ones we can't read without blocking are unmeasured
+        let contents = match context.lock_policy() {
+            LockPolicy::Block => self.read().ok(),
+            LockPolicy::TryLock => self.try_read().ok(),
+        };
+
+        match contents {
+            Some(contents) => contents.size_of_children(context),
+            None => {
+                context.add_unmeasured();
+            }
         }
     }
 }
@@ -184,9 +210,12 @@ impl SizeOf for Once {
         }
 
         // We assume the `Once` only points to a single `Waiter`
-        context
-            .add(size_of::<FakeWaiter>())
-            .add_distinct_allocation();
+        context.attribute_to::<Self, _>(|ctx| {
+            ctx.estimated(|ctx| {
+                ctx.add(size_of::<FakeWaiter>())
+                    .add_allocation(size_of::<FakeWaiter>());
+            });
+        });
     }
 }
 
@@ -260,25 +289,62 @@ pub(crate) mod hashmap {
         }
     }
 
+    /// Adds the estimated table of a hashmap of type `M` with `length` `(K, V)`
+    /// elements and room for `capacity` of them along with its hasher, its
+    /// elements are visited with `visit_elements` if they can have children
+    ///
+    /// Hash sets are hashmaps with `()` values
+    pub(crate) fn size_of_hashmap<M, K, V, S, F>(
+        name: &'static str,
+        length: usize,
+        capacity: usize,
+        hasher: &S,
+        context: &mut Context,
+        visit_elements: F,
+    ) where
+        M: ?Sized,
+        K: SizeOf,
+        V: SizeOf,
+        S: SizeOf,
+        F: FnOnce(&mut Context),
+    {
+        context.attribute_to::<M, _>(|ctx| {
+            ctx.named(name, |ctx| {
+                if capacity != 0 {
+                    let (total_bytes, used_bytes) = estimate_hashmap_size::<K, V>(length, capacity);
+
+                    ctx.estimated(|ctx| {
+                        ctx.add(used_bytes)
+                            .add_excess(total_bytes - used_bytes)
+                            .add_allocation(total_bytes);
+                    });
+
+                    if !(K::IS_CHILDLESS && V::IS_CHILDLESS) {
+                        visit_elements(ctx);
+                    }
+                }
+
+                hasher.size_of_children(ctx);
+            });
+        });
+    }
+
     impl<K, S> SizeOf for HashSet<K, S>
     where
         K: SizeOf,
         S: SizeOf,
     {
         fn size_of_children(&self, context: &mut Context) {
-            if self.capacity() != 0 {
-                let (total_bytes, used_bytes) =
-                    estimate_hashmap_size::<K, ()>(self.len(), self.capacity());
-
-                context
-                    .add(used_bytes)
-                    .add_excess(total_bytes - used_bytes)
-                    .add_distinct_allocation();
-
-                self.iter().for_each(|key| key.size_of_children(context));
-            }
-
-            self.hasher().size_of_children(context);
+            size_of_hashmap::<Self, K, (), S, _>(
+                "HashSet",
+                self.len(),
+                self.capacity(),
+                self.hasher(),
+                context,
+                |ctx| {
+                    ctx.visit_elements(self, |ctx, key| key.size_of_children(ctx));
+                },
+            );
         }
     }
 
@@ -289,22 +355,19 @@ pub(crate) mod hashmap {
         S: SizeOf,
     {
         fn size_of_children(&self, context: &mut Context) {
-            if self.capacity() != 0 {
-                let (total_bytes, used_bytes) =
-                    estimate_hashmap_size::<K, V>(self.len(), self.capacity());
-
-                context
-                    .add(used_bytes)
-                    .add_excess(total_bytes - used_bytes)
-                    .add_distinct_allocation();
-
-                self.iter().for_each(|(key, value)| {
-                    key.size_of_children(context);
-                    value.size_of_children(context);
-                });
-            }
-
-            self.hasher().size_of_children(context);
+            size_of_hashmap::<Self, K, V, S, _>(
+                "HashMap",
+                self.len(),
+                self.capacity(),
+                self.hasher(),
+                context,
+                |ctx| {
+                    ctx.visit_elements(self, |ctx, (key, value)| {
+                        ctx.named("keys", |ctx| key.size_of_children(ctx))
+                            .named("values", |ctx| value.size_of_children(ctx));
+                    });
+                },
+            );
         }
     }
 }
@@ -16,13 +16,18 @@ impl SizeOf for ArcStr {
     fn size_of_children(&self, context: &mut Context) {
         // FIXME: I'd rather use some sort of `ArcStr::as_ptr()` if possible
         if context.insert_ptr(self.as_ptr()) {
+            // The size of the allocation is modeled on arcstr's internals
             let bytes = size_of::<FakeThinInner>() + self.len();
-            context.add(bytes).add_shared(bytes);
-
-            // Static arcstrs don't create any allocations
-            if !ArcStr::is_static(self) {
-                context.add_distinct_allocation();
-            }
+            context.attribute_to::<Self, _>(|ctx| {
+                ctx.estimated(|ctx| {
+                    ctx.add(bytes).add_shared(bytes);
+
+                    // Static arcstrs don't create any allocations
+                    if !ArcStr::is_static(self) {
+                        ctx.add_allocation(bytes);
+                    }
+                });
+            });
         }
     }
 }
@@ -51,19 +56,28 @@ mod tests {
                 0,
                 size_of::<FakeThinInner>(),
                 0,
-            ),
+            )
+            .with_inline_bytes(size_of::<ArcStr>())
+            .with_estimated_bytes(size_of::<FakeThinInner>()),
         );
 
         let shared = size_of::<FakeThinInner>() + 4;
         let total = size_of::<ArcStr>() + shared;
 
         let static_string = arcstr::literal!("whee");
-        assert_eq!(static_string.size_of(), TotalSize::new(total, 0, shared, 0));
+        assert_eq!(
+            static_string.size_of(),
+            TotalSize::new(total, 0, shared, 0)
+                .with_inline_bytes(size_of::<ArcStr>())
+                .with_estimated_bytes(shared)
+        );
 
         let allocated_string = ArcStr::from("whee");
         assert_eq!(
             allocated_string.size_of(),
-            TotalSize::new(total, 0, shared, 1),
+            TotalSize::new(total, 0, shared, 1)
+                .with_inline_bytes(size_of::<ArcStr>())
+                .with_estimated_bytes(shared),
         );
     }
 
@@ -79,7 +93,12 @@ mod tests {
 
         let shared = size_of::<FakeThinInner>() + 4;
         let total = size_of::<ArcStr>() * 4 + shared;
-        assert_eq!(size, TotalSize::new(total, 0, shared, 1));
+        assert_eq!(
+            size,
+            TotalSize::new(total, 0, shared, 1)
+                .with_inline_bytes(size_of::<ArcStr>() * 4)
+                .with_estimated_bytes(shared),
+        );
     }
 
     #[test]
@@ -92,25 +111,36 @@ mod tests {
                 0,
                 size_of::<FakeThinInner>(),
                 0,
-            ),
+            )
+            .with_inline_bytes(size_of::<Substr>())
+            .with_estimated_bytes(size_of::<FakeThinInner>()),
         );
 
         let shared = size_of::<FakeThinInner>() + 4;
         let total = size_of::<Substr>() + shared;
 
         let static_substr = Substr::full(arcstr::literal!("whee"));
-        assert_eq!(static_substr.size_of(), TotalSize::new(total, 0, shared, 0));
+        assert_eq!(
+            static_substr.size_of(),
+            TotalSize::new(total, 0, shared, 0)
+                .with_inline_bytes(size_of::<Substr>())
+                .with_estimated_bytes(shared)
+        );
 
         let allocated_substr = Substr::full(ArcStr::from("whee"));
         assert_eq!(
             allocated_substr.size_of(),
-            TotalSize::new(total, 0, shared, 1),
+            TotalSize::new(total, 0, shared, 1)
+                .with_inline_bytes(size_of::<Substr>())
+                .with_estimated_bytes(shared),
         );
 
         let sliced_allocated_substr = ArcStr::from("whee").substr(..2);
         assert_eq!(
             sliced_allocated_substr.size_of(),
-            TotalSize::new(total, 0, shared, 1),
+            TotalSize::new(total, 0, shared, 1)
+                .with_inline_bytes(size_of::<Substr>())
+                .with_estimated_bytes(shared),
         );
     }
 }
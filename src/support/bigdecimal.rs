@@ -11,9 +11,13 @@ type BigDigit = u32;
 
 impl SizeOf for BigDecimal {
     fn size_of_children(&self, context: &mut Context) {
-        // TODO: There's no way to access allocated capacity
-        context
-            .add_arraylike(self.digits() as usize, size_of::<BigDigit>())
-            .add_distinct_allocation();
+        // TODO: There's no way to access allocated capacity, so this is only
+        // an estimate
+        let digits = self.digits() as usize;
+        context.attribute_to::<Self, _>(|ctx| {
+            ctx.estimated(|ctx| {
+                ctx.add_arraylike_allocation(digits, size_of::<BigDigit>());
+            });
+        });
     }
 }
@@ -1,6 +1,6 @@
 #![cfg(feature = "hashbrown")]
 
-use crate::{std_impls::hashmap::estimate_hashmap_size, Context, SizeOf};
+use crate::{std_impls::hashmap::size_of_hashmap, Context, SizeOf};
 use hashbrown::{HashMap, HashSet};
 
 impl<K, S> SizeOf for HashSet<K, S>
@@ -9,19 +9,16 @@ where
     S: SizeOf,
 {
     fn size_of_children(&self, context: &mut Context) {
-        if self.capacity() != 0 {
-            let (total_bytes, used_bytes) =
-                estimate_hashmap_size::<K, ()>(self.len(), self.capacity());
-
-            context
-                .add(used_bytes)
-                .add_excess(total_bytes - used_bytes)
-                .add_distinct_allocation();
-
-            self.iter().for_each(|key| key.size_of_children(context));
-        }
-
-        self.hasher().size_of_children(context);
+        size_of_hashmap::<Self, K, (), S, _>(
+            "HashSet",
+            self.len(),
+            self.capacity(),
+            self.hasher(),
+            context,
+            |ctx| {
+                ctx.visit_elements(self, |ctx, key| key.size_of_children(ctx));
+            },
+        );
     }
 }
 
@@ -32,21 +29,42 @@ where
     S: SizeOf,
 {
     fn size_of_children(&self, context: &mut Context) {
-        if self.capacity() != 0 {
-            let (total_bytes, used_bytes) =
-                estimate_hashmap_size::<K, V>(self.len(), self.capacity());
-
-            context
-                .add(used_bytes)
-                .add_excess(total_bytes - used_bytes)
-                .add_distinct_allocation();
-
-            self.iter().for_each(|(key, value)| {
-                key.size_of_children(context);
-                value.size_of_children(context);
-            });
-        }
+        size_of_hashmap::<Self, K, V, S, _>(
+            "HashMap",
+            self.len(),
+            self.capacity(),
+            self.hasher(),
+            context,
+            |ctx| {
+                ctx.visit_elements(self, |ctx, (key, value)| {
+                    ctx.named("keys", |ctx| key.size_of_children(ctx))
+                        .named("values", |ctx| value.size_of_children(ctx));
+                });
+            },
+        );
+    }
+}
 
-        self.hasher().size_of_children(context);
+#[cfg(feature = "rayon")]
+impl<K, V, S> crate::ParSizeOf for HashMap<K, V, S>
+where
+    K: crate::ParSizeOf,
+    V: crate::ParSizeOf,
+    S: SizeOf + Sync,
+{
+    fn par_size_of_children(&self, context: &mut Context) {
+        size_of_hashmap::<Self, K, V, S, _>(
+            "HashMap",
+            self.len(),
+            self.capacity(),
+            self.hasher(),
+            context,
+            |ctx| {
+                ctx.par_visit_elements(self, |ctx, (key, value)| {
+                    ctx.named("keys", |ctx| key.par_size_of_children(ctx))
+                        .named("values", |ctx| value.par_size_of_children(ctx));
+                });
+            },
+        );
     }
 }
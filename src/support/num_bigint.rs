@@ -14,19 +14,27 @@ impl_total_size_childless! {
 
 impl SizeOf for BigUint {
     fn size_of_children(&self, context: &mut Context) {
-        // TODO: There's no way to access allocated capacity
-        context
-            .add_arraylike(self.iter_u64_digits().len(), size_of::<u64>())
-            .add_distinct_allocation();
+        // TODO: There's no way to access allocated capacity, so this is only
+        // an estimate
+        let digits = self.iter_u64_digits().len();
+        context.attribute_to::<Self, _>(|ctx| {
+            ctx.estimated(|ctx| {
+                ctx.add_arraylike_allocation(digits, size_of::<u64>());
+            });
+        });
     }
 }
 
 impl SizeOf for BigInt {
     fn size_of_children(&self, context: &mut Context) {
-        // TODO: There's no way to access allocated capacity
-        context
-            .add_arraylike(self.iter_u64_digits().len(), size_of::<u64>())
-            .add_distinct_allocation();
+        // TODO: There's no way to access allocated capacity, so this is only
+        // an estimate
+        let digits = self.iter_u64_digits().len();
+        context.attribute_to::<Self, _>(|ctx| {
+            ctx.estimated(|ctx| {
+                ctx.add_arraylike_allocation(digits, size_of::<u64>());
+            });
+        });
     }
 }
 
@@ -15,6 +15,6 @@ mod tests {
     #[test]
     fn rust_decimal_is_u128() {
         let decimal = Decimal::MIN;
-        assert_eq!(decimal.size_of(), TotalSize::total(size_of::<u128>()));
+        assert_eq!(decimal.size_of(), TotalSize::inline(size_of::<u128>()));
     }
 }
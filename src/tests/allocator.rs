@@ -0,0 +1,208 @@
+//! Checks the allocator models and recorded allocation sizes against the sizes
+//! really requested from the global allocator
+
+use crate::{
+    allocator::{AllocatorModel, GlibcMalloc, Jemalloc, Mimalloc},
+    Context, SizeOf, TotalSize,
+};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    boxed::Box,
+    cell::Cell,
+    mem::size_of,
+    rc::Rc,
+    string::String,
+    sync::Arc,
+    vec::Vec,
+};
+
+/// A global allocator that counts the bytes requested by each thread, tests
+/// run in parallel so the counts have to be thread local
+struct CountingAllocator;
+
+std::thread_local! {
+    static ALLOCATED: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATED.try_with(|allocated| allocated.set(allocated.get() + layout.size()));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Returns the value created by `make` along with the number of bytes it
+/// allocated
+fn allocated_by<T, F>(make: F) -> (T, usize)
+where
+    F: FnOnce() -> T,
+{
+    let start = ALLOCATED.with(Cell::get);
+    let value = make();
+    (value, ALLOCATED.with(Cell::get) - start)
+}
+
+/// Asserts that the heap bytes recorded for `value` are the bytes it allocated
+fn assert_heap_bytes<T: SizeOf>(value: &T, allocated: usize, allocations: usize) {
+    let size = value.size_of();
+    assert_eq!(size.total_bytes() - size_of::<T>(), allocated);
+    assert_eq!(size.distinct_allocations(), allocations);
+}
+
+#[test]
+fn rc_control_blocks() {
+    let (rc, allocated) = allocated_by(|| Rc::new(1u8));
+    assert_heap_bytes(&rc, allocated, 1);
+
+    let (rc, allocated) = allocated_by(|| Rc::new([0u16; 3]));
+    assert_heap_bytes(&rc, allocated, 1);
+
+    let (rc, allocated) = allocated_by(|| Rc::<str>::from("hello"));
+    assert_heap_bytes(&rc, allocated, 1);
+
+    let elements = [0u32; 7];
+    let (rc, allocated) = allocated_by(|| Rc::<[u32]>::from(&elements[..]));
+    assert_heap_bytes(&rc, allocated, 1);
+}
+
+#[test]
+fn arc_control_blocks() {
+    let (arc, allocated) = allocated_by(|| Arc::new(1u8));
+    assert_heap_bytes(&arc, allocated, 1);
+
+    let (arc, allocated) = allocated_by(|| Arc::new(1u128));
+    assert_heap_bytes(&arc, allocated, 1);
+
+    let (arc, allocated) = allocated_by(|| Arc::<str>::from("hello, world"));
+    assert_heap_bytes(&arc, allocated, 1);
+
+    let elements = [0u8; 13];
+    let (arc, allocated) = allocated_by(|| Arc::<[u8]>::from(&elements[..]));
+    assert_heap_bytes(&arc, allocated, 1);
+
+    // Empty slices still allocate their counts
+    let (arc, allocated) = allocated_by(|| Arc::<[u64]>::from(Vec::new()));
+    assert_heap_bytes(&arc, allocated, 1);
+
+    let (arc, allocated) = allocated_by(|| Arc::new(String::from("0123456789")));
+    assert_heap_bytes(&arc, allocated, 2);
+
+    // All of the `ArcInner` is shared
+    let size = arc.size_of();
+    assert_eq!(
+        size,
+        TotalSize::new(size_of::<Arc<String>>() + allocated, 0, allocated, 2)
+            .with_inline_bytes(size_of::<Arc<String>>()),
+    );
+}
+
+#[test]
+#[cfg(target_pointer_width = "64")]
+fn glibc_size_classes() {
+    let classes = [
+        (1, 24),
+        (24, 24),
+        (25, 40),
+        (40, 40),
+        (100, 104),
+        (4000, 4008),
+        (128 * 1024, 135152),
+    ];
+
+    for (size, expected) in classes {
+        assert_eq!(GlibcMalloc.usable_size(size), expected, "{size}");
+    }
+}
+
+#[test]
+#[cfg(target_arch = "x86")]
+fn glibc_i386_size_classes() {
+    // Chunks are aligned to 16 bytes even though `usize` is 4 bytes
+    let classes = [(1, 12), (12, 12), (13, 28), (100, 108), (4000, 4012)];
+
+    for (size, expected) in classes {
+        assert_eq!(GlibcMalloc.usable_size(size), expected, "{size}");
+    }
+}
+
+#[test]
+fn huge_sizes() {
+    // Sizes that can't be allocated aren't rounded and don't overflow
+    for size in [usize::MAX - 4, usize::MAX] {
+        assert!(GlibcMalloc.usable_size(size) >= size);
+        assert!(Jemalloc.usable_size(size) >= size);
+        assert!(Mimalloc.usable_size(size) >= size);
+    }
+}
+
+#[test]
+fn jemalloc_size_classes() {
+    let classes = [
+        (1, 8),
+        (9, 16),
+        (17, 32),
+        (100, 112),
+        (129, 160),
+        (256, 256),
+        (257, 320),
+        (4000, 4096),
+        (4097, 5120),
+    ];
+
+    for (size, expected) in classes {
+        assert_eq!(Jemalloc.usable_size(size), expected, "{size}");
+    }
+}
+
+#[test]
+#[cfg(target_pointer_width = "64")]
+fn mimalloc_size_classes() {
+    let classes = [
+        (1, 8),
+        (20, 24),
+        (64, 64),
+        (65, 80),
+        (129, 160),
+        (4000, 4096),
+        (200 * 1024, 204800),
+    ];
+
+    for (size, expected) in classes {
+        assert_eq!(Mimalloc.usable_size(size), expected, "{size}");
+    }
+}
+
+#[test]
+fn context_overhead() {
+    let boxed = Box::new([0u8; 4000]);
+
+    let mut context = Context::new();
+    context.set_allocator_model(Jemalloc);
+    boxed.size_of_with_context(&mut context);
+
+    let expected = TotalSize::new(size_of::<Box<[u8; 4000]>>() + 4000, 0, 0, 1)
+        .with_overhead_bytes(96)
+        .with_inline_bytes(size_of::<Box<[u8; 4000]>>());
+    assert_eq!(context.total_size(), expected);
+    assert_eq!(
+        context.total_size().rounded_bytes(),
+        size_of::<Box<[u8; 4000]>>() + 4096,
+    );
+
+    // Without a model there's no overhead
+    assert_eq!(boxed.size_of().overhead_bytes(), 0);
+
+    // Vectors are rounded by their capacity
+    let vec: Vec<u32> = Vec::with_capacity(33);
+    let mut context = Context::new();
+    context.set_allocator_model(Jemalloc);
+    vec.size_of_with_context(&mut context);
+    assert_eq!(context.total_size().overhead_bytes(), 160 - 132);
+}
@@ -0,0 +1,20 @@
+use crate::histogram::{bucket_of, AllocationHistogram, BUCKETS};
+
+#[test]
+fn buckets() {
+    assert_eq!(bucket_of(0), 0);
+    assert_eq!(bucket_of(1), 0);
+    assert_eq!(bucket_of(2), 1);
+    assert_eq!(bucket_of(3), 2);
+    assert_eq!(bucket_of(4), 2);
+    assert_eq!(bucket_of(5), 3);
+    assert_eq!(bucket_of(4096), 12);
+    assert_eq!(bucket_of(4097), 13);
+    assert_eq!(bucket_of(usize::MAX), BUCKETS - 1);
+
+    let histogram = AllocationHistogram::new();
+    for size in [0, 1, 2, 3, 4, 5, 100, 4096, 4097, usize::MAX] {
+        let bucket = histogram.bucket(size);
+        assert!(bucket.min_size() <= size && size <= bucket.max_size());
+    }
+}
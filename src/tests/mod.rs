@@ -1,47 +1,69 @@
 #![cfg(test)]
 
-use crate::{collections::btree::estimate_btree_size, SizeOf, TotalSize};
+use crate::{
+    collections::btree::estimate_btree_size, ByteMetric, Context, DotSink, DynSizeOf,
+    SharedAttribution, SizeDiff, SizeNode, SizeOf, SizeReport, SizeSink, TotalSize, TypeSize,
+    WeakPolicy,
+};
 use alloc::{
     boxed::Box,
-    collections::{BTreeMap, BTreeSet, BinaryHeap, VecDeque},
-    string::String,
-    sync::Arc,
+    collections::{BTreeMap, BTreeSet, BinaryHeap, LinkedList, VecDeque},
+    format,
+    rc::{Rc, Weak as RcWeak},
+    string::{String, ToString},
+    sync::{Arc, Weak as ArcWeak},
     vec,
     vec::Vec,
 };
-use core::mem::{size_of, size_of_val};
+use core::{
+    any::type_name,
+    mem::{size_of, size_of_val},
+};
+
+/// Measures `value` with a new context that's set up by `configure`
+fn size_with<T, F>(value: &T, configure: F) -> Context
+where
+    T: SizeOf + ?Sized,
+    F: FnOnce(&mut Context) -> &mut Context,
+{
+    let mut context = Context::new();
+    configure(&mut context);
+    value.size_of_with_context(&mut context);
+    context
+}
 
 #[test]
+#[allow(clippy::size_of_ref)]
 fn primitives() {
-    assert_eq!(0u8.size_of(), TotalSize::total(1));
-    assert_eq!(0u16.size_of(), TotalSize::total(2));
-    assert_eq!(0u32.size_of(), TotalSize::total(4));
-    assert_eq!(0u64.size_of(), TotalSize::total(8));
-    assert_eq!(0usize.size_of(), TotalSize::total(size_of::<usize>()));
+    assert_eq!(0u8.size_of(), TotalSize::inline(1));
+    assert_eq!(0u16.size_of(), TotalSize::inline(2));
+    assert_eq!(0u32.size_of(), TotalSize::inline(4));
+    assert_eq!(0u64.size_of(), TotalSize::inline(8));
+    assert_eq!(0usize.size_of(), TotalSize::inline(size_of::<usize>()));
 
-    assert_eq!(0i8.size_of(), TotalSize::total(1));
-    assert_eq!(0i16.size_of(), TotalSize::total(2));
-    assert_eq!(0i32.size_of(), TotalSize::total(4));
-    assert_eq!(0i64.size_of(), TotalSize::total(8));
-    assert_eq!(0isize.size_of(), TotalSize::total(size_of::<isize>()));
+    assert_eq!(0i8.size_of(), TotalSize::inline(1));
+    assert_eq!(0i16.size_of(), TotalSize::inline(2));
+    assert_eq!(0i32.size_of(), TotalSize::inline(4));
+    assert_eq!(0i64.size_of(), TotalSize::inline(8));
+    assert_eq!(0isize.size_of(), TotalSize::inline(size_of::<isize>()));
 
-    assert_eq!(0f32.size_of(), TotalSize::total(4));
-    assert_eq!(0f64.size_of(), TotalSize::total(8));
+    assert_eq!(0f32.size_of(), TotalSize::inline(4));
+    assert_eq!(0f64.size_of(), TotalSize::inline(8));
 
-    assert_eq!('f'.size_of(), TotalSize::total(4));
-    assert_eq!("Hello World!".size_of(), TotalSize::total(12));
+    assert_eq!('f'.size_of(), TotalSize::inline(4));
+    assert_eq!("Hello World!".size_of(), TotalSize::inline(12));
     assert_eq!(
         (&"Hello World!").size_of(),
-        TotalSize::total(size_of_val::<&str>(&"Hello World!")),
+        TotalSize::inline(size_of_val::<&str>(&"Hello World!")),
     );
-    assert_eq!(true.size_of(), TotalSize::total(1));
+    assert_eq!(true.size_of(), TotalSize::inline(1));
 }
 
 #[test]
 fn boxed() {
     assert_eq!(
         Box::new(0u32).size_of(),
-        TotalSize::new(4 + size_of::<usize>(), 0, 0, 1),
+        TotalSize::new(4 + size_of::<usize>(), 0, 0, 1).with_inline_bytes(size_of::<usize>()),
     );
 }
 
@@ -50,18 +72,19 @@ fn boxed_zst() {
     assert_eq!(
         Box::new(()).size_of(),
         // Just the size of the pointer, no heap allocations
-        TotalSize::total(size_of::<Box<()>>()),
+        TotalSize::inline(size_of::<Box<()>>()),
     );
 }
 
 #[test]
 fn slices() {
     let array: Box<[u32]> = vec![0; 64].into_boxed_slice();
-    assert_eq!(array[5..10].size_of(), TotalSize::total(4 * 5));
-    assert_eq!(array[..32].size_of(), TotalSize::total(4 * 32));
+    assert_eq!(array[5..10].size_of(), TotalSize::inline(4 * 5));
+    assert_eq!(array[..32].size_of(), TotalSize::inline(4 * 32));
     assert_eq!(
         <Box<_> as SizeOf>::size_of(&array),
-        TotalSize::new(size_of::<Box<[u32]>>() + size_of::<[u32; 64]>(), 0, 0, 1),
+        TotalSize::new(size_of::<Box<[u32]>>() + size_of::<[u32; 64]>(), 0, 0, 1)
+            .with_inline_bytes(size_of::<Box<[u32]>>()),
     );
 
     let array: Box<[u32; 1000]> = vec![0; 1000].into_boxed_slice().try_into().unwrap();
@@ -72,43 +95,48 @@ fn slices() {
             0,
             0,
             1,
-        ),
+        )
+        .with_inline_bytes(size_of::<Box<[u32; 1000]>>()),
     );
 }
 
 #[test]
 fn vec() {
     let vec: Vec<u32> = vec![0; 64];
-    assert_eq!(vec[5..10].size_of(), TotalSize::total(4 * 5));
-    assert_eq!(vec[..32].size_of(), TotalSize::total(4 * 32));
+    assert_eq!(vec[5..10].size_of(), TotalSize::inline(4 * 5));
+    assert_eq!(vec[..32].size_of(), TotalSize::inline(4 * 32));
     assert_eq!(
         vec.size_of(),
-        TotalSize::new(size_of::<Vec<u32>>() + size_of::<[u32; 64]>(), 0, 0, 1),
+        TotalSize::new(size_of::<Vec<u32>>() + size_of::<[u32; 64]>(), 0, 0, 1)
+            .with_inline_bytes(size_of::<Vec<u32>>()),
     );
 
     let mut overallocated = Vec::with_capacity(1000);
     assert_eq!(
         overallocated.size_of(),
-        TotalSize::new(size_of::<Vec<u8>>() + 1000, 1000, 0, 1),
+        TotalSize::new(size_of::<Vec<u8>>() + 1000, 1000, 0, 1)
+            .with_inline_bytes(size_of::<Vec<u8>>()),
     );
 
     overallocated.extend(0u8..100);
     assert_eq!(
         overallocated.size_of(),
-        TotalSize::new(size_of::<Vec<u8>>() + 1000, 900, 0, 1),
+        TotalSize::new(size_of::<Vec<u8>>() + 1000, 900, 0, 1)
+            .with_inline_bytes(size_of::<Vec<u8>>()),
     );
 
     let mut vec_o_vecs = Vec::new();
     assert_eq!(
         vec_o_vecs.size_of(),
-        TotalSize::total(size_of::<Vec<Vec<u8>>>()),
+        TotalSize::inline(size_of::<Vec<Vec<u8>>>()),
     );
 
     vec_o_vecs.reserve_exact(1);
     vec_o_vecs.push(Vec::new());
     assert_eq!(
         vec_o_vecs.size_of(),
-        TotalSize::new(size_of::<Vec<Vec<u8>>>() + size_of::<Vec<u8>>(), 0, 0, 1),
+        TotalSize::new(size_of::<Vec<Vec<u8>>>() + size_of::<Vec<u8>>(), 0, 0, 1)
+            .with_inline_bytes(size_of::<Vec<Vec<u8>>>()),
     );
 
     vec_o_vecs[0].reserve_exact(1000);
@@ -120,7 +148,8 @@ fn vec() {
             900,
             0,
             2,
-        ),
+        )
+        .with_inline_bytes(size_of::<Vec<Vec<u8>>>()),
     );
 }
 
@@ -129,18 +158,21 @@ fn collections_of_zsts() {
     assert_eq!(
         vec![(), (), (), ()].size_of(),
         // Nothing but the vec's `{ ptr, len, cap }`, no heap allocation should be recorded
-        TotalSize::total(size_of::<Vec<()>>()),
+        TotalSize::inline(size_of::<Vec<()>>()),
     );
 
     let mut queue = VecDeque::new();
     queue.extend([(), (), (), (), ()]);
-    assert_eq!(queue.size_of(), TotalSize::total(size_of::<VecDeque<()>>()));
+    assert_eq!(
+        queue.size_of(),
+        TotalSize::inline(size_of::<VecDeque<()>>())
+    );
 
     let mut heap = BinaryHeap::new();
     heap.extend([(), (), (), (), ()]);
     assert_eq!(
         heap.size_of(),
-        TotalSize::total(size_of::<BinaryHeap<()>>()),
+        TotalSize::inline(size_of::<BinaryHeap<()>>()),
     );
 }
 
@@ -149,34 +181,42 @@ fn strings() {
     let string_a = String::from("01234567");
     assert_eq!(
         string_a.size_of(),
-        TotalSize::new(size_of::<String>() + 8, 0, 0, 1),
+        TotalSize::new(size_of::<String>() + 8, 0, 0, 1).with_inline_bytes(size_of::<String>()),
     );
 
     let string_b = String::from("0123456789012345");
     assert_eq!(
         string_b.size_of(),
-        TotalSize::new(size_of::<String>() + 16, 0, 0, 1),
+        TotalSize::new(size_of::<String>() + 16, 0, 0, 1).with_inline_bytes(size_of::<String>()),
     );
 
     let mut overallocated = String::with_capacity(1000);
     assert_eq!(
         overallocated.size_of(),
-        TotalSize::new(size_of::<String>() + 1000, 1000, 0, 1),
+        TotalSize::new(size_of::<String>() + 1000, 1000, 0, 1)
+            .with_inline_bytes(size_of::<String>()),
     );
 
     overallocated.push_str("0123456789012345");
     assert_eq!(
         overallocated.size_of(),
-        TotalSize::new(size_of::<String>() + 1000, 1000 - 16, 0, 1),
+        TotalSize::new(size_of::<String>() + 1000, 1000 - 16, 0, 1)
+            .with_inline_bytes(size_of::<String>()),
     );
 }
 
 #[test]
 fn arc() {
+    // An `ArcInner` holds the strong and weak counts followed by the value
+    let counts = size_of::<usize>() * 2;
+
     let arc_u8 = Arc::new(1_u8);
+    // The single byte is padded to the alignment of the counts
+    let inner_bytes = counts + size_of::<usize>();
     assert_eq!(
         arc_u8.size_of(),
-        TotalSize::new(size_of::<Arc<u8>>() + 1, 0, 1, 1),
+        TotalSize::new(size_of::<Arc<u8>>() + inner_bytes, 0, inner_bytes, 1)
+            .with_inline_bytes(size_of::<Arc<u8>>()),
     );
     assert_eq!(Arc::clone(&arc_u8).size_of(), arc_u8.size_of());
 
@@ -184,21 +224,22 @@ fn arc() {
     let string_size = string.size_of();
     assert_eq!(
         string_size,
-        TotalSize::new(size_of::<String>() + 16, 0, 0, 1),
+        TotalSize::new(size_of::<String>() + 16, 0, 0, 1).with_inline_bytes(size_of::<String>()),
     );
 
     // Internally an arc is made of a usize
     let arc_bytes = size_of::<usize>();
     // The total size if the size of the Arc plus the size of the
-    // String.
-    let total_bytes = string_size.total_bytes() + arc_bytes;
+    // String and the counts.
+    let total_bytes = string_size.total_bytes() + arc_bytes + counts;
     let excess_bytes = 0;
-    // The whole string is shared
-    let shared_bytes = string_size.total_bytes();
+    // The whole string is shared along with the counts
+    let shared_bytes = string_size.total_bytes() + counts;
     // There were two allocations: one for the string, one for the
     // Arc.
     let allocations = 2;
-    let mut total_size = TotalSize::new(total_bytes, excess_bytes, shared_bytes, allocations);
+    let mut total_size = TotalSize::new(total_bytes, excess_bytes, shared_bytes, allocations)
+        .with_inline_bytes(arc_bytes);
 
     let arc_string = Arc::new(string);
     assert_eq!(arc_string.size_of(), total_size);
@@ -208,22 +249,776 @@ fn arc() {
     let tuple = (arc_string, arc_string_clone);
     // Total size is incremented by size_of<Arc<_>>
     // There is also no new allocation
-    total_size += TotalSize::total(arc_bytes);
+    total_size += TotalSize::inline(arc_bytes);
     assert_eq!(tuple.size_of(), total_size,);
 }
 
+#[test]
+fn proportional_attribution() {
+    fn proportional(context: &mut Context) -> &mut Context {
+        context.set_shared_attribution(SharedAttribution::Proportional)
+    }
+    fn first_owner(context: &mut Context) -> &mut Context {
+        context.set_shared_attribution(SharedAttribution::FirstOwner)
+    }
+
+    let arc = Arc::new(String::from("0123456789012345"));
+    let owners = [arc.clone(), arc.clone(), arc.clone(), arc];
+    let arc_bytes = size_of::<Arc<String>>();
+
+    // The `ArcInner` plus the string's buffer
+    let shared = (size_of::<usize>() * 2) + size_of::<String>() + 16;
+    let full = TotalSize::new(shared, 0, shared, 2);
+
+    // Each owner is charged a quarter of the allocation, the first owner also
+    // gets the remainder
+    let quarter = TotalSize::new(shared / 4, 0, shared / 4, 0);
+    let first = quarter + TotalSize::new(shared % 4, 0, shared % 4, 2);
+    assert_eq!(
+        size_with(&owners[0], proportional).total_size(),
+        TotalSize::inline(arc_bytes) + first,
+    );
+    assert_eq!(
+        size_with(&owners[..2], proportional).total_size(),
+        TotalSize::inline(arc_bytes * 2) + first + quarter,
+    );
+
+    // Every owner's charge is the same regardless of which owner is seen first
+    let mut context = Context::new();
+    context.set_shared_attribution(SharedAttribution::Proportional);
+    owners[3].size_of_with_context(&mut context);
+    let before = context.total_size();
+    owners[1].size_of_with_context(&mut context);
+    assert_eq!(
+        context.total_size() - before,
+        TotalSize::inline(arc_bytes) + quarter
+    );
+
+    // All owners add up to the full allocation
+    assert_eq!(
+        size_with(&owners, proportional).total_size(),
+        TotalSize::inline(arc_bytes * 4) + full,
+    );
+    assert_eq!(
+        size_with(&owners, first_owner).total_size(),
+        size_with(&owners, proportional).total_size(),
+    );
+    assert_eq!(
+        size_with(&owners[1], first_owner).total_size(),
+        TotalSize::inline(arc_bytes) + full,
+    );
+
+    // The byte budget still applies within shared allocations
+    let value = (
+        vec![0u8; 4096],
+        Arc::new(vec![String::from("0123456789012345"); 100]),
+    );
+    let mut context = Context::new();
+    context
+        .set_shared_attribution(SharedAttribution::Proportional)
+        .set_byte_budget(Some(4096 + 1024));
+    value.size_of_with_context(&mut context);
+    assert_eq!(context.total_size().truncated_values(), 1);
+}
+
+#[test]
+fn retained_sizes() {
+    let shared = Arc::new(String::from("0123456789012345"));
+    let nested = Arc::new(vec![shared.clone(), Arc::new(String::from("nested"))]);
+    let first_root = (shared.clone(), nested);
+    let second_root = vec![shared];
+
+    let report = crate::retained_sizes([
+        ("first", &first_root as &dyn DynSizeOf),
+        ("second", &second_root as &dyn DynSizeOf),
+        ("third", &10u64 as &dyn DynSizeOf),
+    ]);
+    assert_eq!(report.len(), 3);
+
+    // The `ArcInner<String>` plus its buffer
+    let counts = size_of::<usize>() * 2;
+    let shared_string = TotalSize::new(
+        counts + size_of::<String>() + 16,
+        0,
+        counts + size_of::<String>() + 16,
+        2,
+    );
+
+    let first = report.get("first").unwrap();
+    assert_eq!(first.shared(), shared_string);
+    assert_eq!(first.total(), first_root.size_of());
+    assert_eq!(first.exclusive(), first.total() - shared_string);
+
+    let second = report.get("second").unwrap();
+    assert_eq!(second.shared(), shared_string);
+    assert_eq!(
+        second.exclusive(),
+        TotalSize::new(size_of::<Vec<Arc<String>>>() + size_of::<usize>(), 0, 0, 1)
+            .with_inline_bytes(size_of::<Vec<Arc<String>>>()),
+    );
+
+    let third = report.get("third").unwrap();
+    assert_eq!(third.exclusive(), TotalSize::inline(8));
+    assert_eq!(third.shared(), TotalSize::zero());
+}
+
+#[test]
+fn size_diff() {
+    let before = TotalSize::new(64, 8, 0, 2);
+    let after = TotalSize::new(32, 16, 0, 1);
+    let diff = SizeDiff::new(before, after);
+    assert_eq!(diff.total_bytes(), -32);
+    assert_eq!(diff.excess_bytes(), 8);
+    assert_eq!(diff.distinct_allocations(), -1);
+    assert!(diff.shrank() && !diff.grew());
+    assert_eq!(format!("{diff}"), "-32 B (64 B -> 32 B), -1 allocations");
+
+    // Deltas saturate instead of overflowing
+    let huge = SizeDiff::new(TotalSize::zero(), TotalSize::total(usize::MAX));
+    assert_eq!(huge.total_bytes(), isize::MAX);
+    assert!(SizeDiff::new(after, after).is_unchanged());
+    assert!(!SizeDiff::new(TotalSize::total(8), TotalSize::inline(8)).is_unchanged());
+
+    let mut buffers = vec![vec![0u8; 16], vec![0u8; 16], vec![0u8; 16]];
+    let unchanged = String::from("unchanged");
+    let roots = |buffers: &Vec<Vec<u8>>| {
+        crate::retained_sizes([
+            ("first", &buffers[0] as &dyn DynSizeOf),
+            ("second", &buffers[1] as &dyn DynSizeOf),
+            ("third", &buffers[2] as &dyn DynSizeOf),
+            ("unchanged", &unchanged as &dyn DynSizeOf),
+        ])
+    };
+
+    let before = roots(&buffers);
+    buffers[0].reserve_exact(1024);
+    buffers[2] = Vec::new();
+    let report = before.diff(&roots(&buffers));
+
+    // The biggest changes come first
+    let names: Vec<_> = report.iter().map(|entry| entry.name()).collect();
+    assert_eq!(names, ["first", "third", "second", "unchanged"]);
+    assert_eq!(report.get("first").unwrap().diff().total_bytes(), 1024);
+    assert_eq!(report.get("third").unwrap().diff().total_bytes(), -16);
+    assert_eq!(report.grown().count(), 1);
+    assert_eq!(report.shrunk().count(), 1);
+    assert_eq!(report.total().total_bytes(), 1024 - 16);
+
+    let display = format!("{report}");
+    assert!(display.lines().nth(1).unwrap().ends_with("first"));
+    assert!(display.ends_with("2 unchanged\n"));
+}
+
+#[test]
+fn weak_pointers() {
+    fn shared_if_live(context: &mut Context) -> &mut Context {
+        context.set_weak_policy(WeakPolicy::SharedIfLive)
+    }
+    fn retained_control_block(context: &mut Context) -> &mut Context {
+        context.set_weak_policy(WeakPolicy::RetainedControlBlock)
+    }
+
+    let weak_bytes = size_of::<RcWeak<u64>>();
+    // The `RcBox` holding the strong and weak counts and the value
+    let block = (size_of::<usize>() * 2) + 8;
+    let rc = Rc::new(10u64);
+    let weak = Rc::downgrade(&rc);
+
+    // Weak pointers are ignored by default
+    assert_eq!(weak.size_of(), TotalSize::inline(weak_bytes));
+
+    // Live pointees are shared
+    assert_eq!(
+        size_with(&weak, shared_if_live).total_size(),
+        TotalSize::new(weak_bytes + block, 0, block, 1).with_inline_bytes(weak_bytes),
+    );
+    // The control block is owned by the strong pointer
+    assert_eq!(
+        size_with(&weak, retained_control_block).total_size(),
+        TotalSize::inline(weak_bytes),
+    );
+    // Weaks are deduplicated with their strong pointers
+    assert_eq!(
+        size_with(&(Rc::clone(&rc), weak.clone()), shared_if_live).total_size(),
+        TotalSize::new(size_of::<Rc<u64>>() + weak_bytes + block, 0, block, 1)
+            .with_inline_bytes(size_of::<Rc<u64>>() + weak_bytes),
+    );
+
+    // Weak pointers aren't charged a share of their pointee, so the shares of
+    // the strong pointers add up to the allocation
+    let proportional = |value: &dyn DynSizeOf| {
+        let mut context = Context::new();
+        context
+            .set_weak_policy(WeakPolicy::SharedIfLive)
+            .set_shared_attribution(SharedAttribution::Proportional);
+        value.size_of_with_context(&mut context);
+        context.total_size()
+    };
+    let shared = Rc::new(10u64);
+    let owners = (
+        Rc::downgrade(&shared),
+        Rc::clone(&shared),
+        Rc::downgrade(&shared),
+        shared,
+    );
+    assert_eq!(proportional(&owners.0), TotalSize::inline(weak_bytes));
+    assert_eq!(
+        proportional(&owners),
+        TotalSize::new(size_of_val(&owners) + block, 0, block, 1)
+            .with_inline_bytes(size_of_val(&owners)),
+    );
+
+    // Once the pointee is dropped only the control block remains
+    drop(rc);
+    assert_eq!(
+        size_with(&weak, shared_if_live).total_size(),
+        TotalSize::inline(weak_bytes),
+    );
+    assert_eq!(
+        size_with(&(weak.clone(), weak), retained_control_block).total_size(),
+        TotalSize::new((weak_bytes * 2) + block, 0, block, 1).with_inline_bytes(weak_bytes * 2),
+    );
+
+    // `Weak::new()` doesn't allocate anything
+    assert_eq!(
+        size_with(&ArcWeak::<u64>::new(), retained_control_block).total_size(),
+        TotalSize::inline(size_of::<ArcWeak<u64>>()),
+    );
+
+    let arc = Arc::new(String::from("0123456789012345"));
+    let weak = Arc::downgrade(&arc);
+    drop(arc);
+    let block = (size_of::<usize>() * 2) + size_of::<String>();
+    assert_eq!(
+        size_with(&weak, retained_control_block).total_size(),
+        TotalSize::new(size_of::<ArcWeak<String>>() + block, 0, block, 1)
+            .with_inline_bytes(size_of::<ArcWeak<String>>()),
+    );
+}
+
+#[test]
+#[allow(clippy::assertions_on_constants)]
+fn childless() {
+    const {
+        assert!(u8::IS_CHILDLESS);
+        assert!(<(u8, u32, f64)>::IS_CHILDLESS);
+        assert!(<[u64; 4]>::IS_CHILDLESS);
+        assert!(<Option<Result<u32, bool>>>::IS_CHILDLESS);
+        assert!(<*const String>::IS_CHILDLESS);
+        assert!(str::IS_CHILDLESS);
+        assert!(<[u64]>::IS_CHILDLESS);
+        assert!(!<[String]>::IS_CHILDLESS);
+        assert!(!String::IS_CHILDLESS);
+        assert!(!<Vec<u8>>::IS_CHILDLESS);
+        assert!(!<(u8, Box<u8>)>::IS_CHILDLESS);
+        assert!(!<&u8>::IS_CHILDLESS);
+    }
+
+    // Collections don't visit childless elements
+    #[derive(Clone)]
+    #[allow(dead_code)]
+    struct Childless(u32);
+
+    impl SizeOf for Childless {
+        fn size_of_children(&self, _context: &mut Context) {
+            unreachable!("childless elements shouldn't be visited")
+        }
+
+        const IS_CHILDLESS: bool = true;
+    }
+
+    let vec = vec![Childless(0); 100];
+    assert_eq!(
+        vec.size_of(),
+        TotalSize::new(size_of::<Vec<Childless>>() + 400, 0, 0, 1)
+            .with_inline_bytes(size_of::<Vec<Childless>>()),
+    );
+    let map: BTreeMap<u32, Childless> = (0..100).map(|key| (key, Childless(key))).collect();
+    map.size_of();
+    let deque: VecDeque<Childless> = vec.into_iter().collect();
+    deque.size_of();
+}
+
+#[test]
+fn sampling() {
+    // Elements with identical children are estimated exactly
+    let strings: Vec<String> = (0..1000).map(|i| format!("{i:0>16}")).collect();
+    let context = size_with(&strings, |ctx| ctx.set_sample_size(Some(10)));
+    assert_eq!(
+        context.total_size(),
+        strings.size_of().with_estimated_bytes(990 * 16),
+    );
+
+    let report = context.sampling_report();
+    assert!(report.is_estimate());
+    assert_eq!(report.sampled_collections(), 1);
+    assert_eq!(report.sampled_elements(), 10);
+    assert_eq!(report.total_elements(), 1000);
+    assert_eq!(report.estimated_bytes(), 990 * 16);
+    assert_eq!(report.standard_error(), 0.0);
+    assert_eq!(
+        report.confidence_interval_95(),
+        (
+            strings.size_of().total_bytes(),
+            strings.size_of().total_bytes()
+        ),
+    );
+
+    // Collections within the sample size are measured exactly
+    let context = size_with(&strings, |ctx| ctx.set_sample_size(Some(1000)));
+    assert_eq!(context.total_size(), strings.size_of());
+    assert!(!context.sampling_report().is_estimate());
+
+    // Differently sized children give an interval around the estimate
+    let vecs: Vec<Vec<u8>> = (0..10_000).map(|i| vec![0; i % 100]).collect();
+    let exact = vecs.size_of().total_bytes();
+    let report = size_with(&vecs, |ctx| ctx.set_sample_size(Some(500))).sampling_report();
+    let (lower, upper) = report.confidence_interval_95();
+    assert!(report.standard_error() > 0.0);
+    assert!(lower <= report.total_bytes() && report.total_bytes() <= upper);
+    assert!(
+        lower <= exact && exact <= upper,
+        "{lower} <= {exact} <= {upper}"
+    );
+
+    // Maps are sampled too
+    let map: BTreeMap<u32, String> = (0..1000).map(|i| (i, format!("{i:0>16}"))).collect();
+    let size = map.size_of();
+    assert_eq!(
+        size_with(&map, |ctx| ctx.set_sample_size(Some(10))).total_size(),
+        size.with_estimated_bytes(size.estimated_bytes() + 990 * 16),
+    );
+}
+
+#[test]
+fn estimated_bytes() {
+    // Exact sources aren't estimated
+    let vec = vec![0u8; 100];
+    assert_eq!(vec.size_of().estimated_bytes(), 0);
+    assert_eq!(vec.size_of().exact_bytes(), vec.size_of().total_bytes());
+
+    // Modeled sources are estimated while their children aren't
+    let set: BTreeSet<String> = (0..10).map(|i| format!("{i:0>16}")).collect();
+    let size = set.size_of();
+    assert_eq!(
+        size.estimated_bytes(),
+        estimate_btree_size::<String, ()>(10).unwrap()
+    );
+    assert_eq!(size.exact_bytes(), size_of::<BTreeSet<String>>() + 10 * 16,);
+
+    let list: LinkedList<String> = (0..10).map(|i| format!("{i:0>16}")).collect();
+    let size = list.size_of();
+    let node = size_of::<String>() + size_of::<*const ()>() * 2;
+    assert_eq!(size.estimated_bytes(), node * 10);
+    assert_eq!(
+        size.exact_bytes(),
+        size_of::<LinkedList<String>>() + 10 * 16
+    );
+
+    // Custom impls can mark their own bytes as estimated
+    let mut context = Context::new();
+    context.add(8).estimated(|ctx| {
+        assert!(ctx.is_estimated());
+        ctx.add(16).add_excess(4);
+    });
+    assert!(!context.is_estimated());
+    assert_eq!(
+        context.total_size(),
+        TotalSize::new(28, 4, 0, 0).with_estimated_bytes(20),
+    );
+}
+
+#[test]
+fn inline_bytes() {
+    let mut vec = Vec::with_capacity(16);
+    vec.push(String::from("0123456789"));
+    let size = vec.size_of();
+    assert_eq!(size.inline_bytes(), size_of::<Vec<String>>());
+    assert_eq!(size.heap_bytes(), size_of::<String>() * 16 + 10);
+    assert_eq!(size.total_bytes(), size.inline_bytes() + size.heap_bytes());
+
+    // Every measured value adds its own inline bytes
+    let size = crate::size_of_values([&vec as &dyn DynSizeOf, &0u64 as &dyn DynSizeOf]);
+    assert_eq!(size.inline_bytes(), size_of::<Vec<String>>() + 8);
+
+    // Borrowed values aren't inline
+    let mut context = Context::new();
+    context.set_follow_references(true);
+    <&Vec<String> as SizeOf>::size_of_with_context(&&vec, &mut context);
+    let size = context.total_size();
+    assert_eq!(size.inline_bytes(), size_of::<&Vec<String>>());
+    assert_eq!(size.heap_bytes(), 0);
+
+    let mut context = Context::new();
+    context.add_inline(8).add(16);
+    assert_eq!(
+        context.total_size(),
+        TotalSize::total(24).with_inline_bytes(8)
+    );
+    assert_ne!(TotalSize::inline(8), TotalSize::total(8));
+}
+
+#[test]
+fn sinks() {
+    /// Records the bytes directly added within each named node
+    #[derive(Debug, Default)]
+    struct Nodes {
+        path: Vec<&'static str>,
+        bytes: BTreeMap<String, usize>,
+    }
+
+    impl SizeSink for Nodes {
+        fn record(&mut self, size: TotalSize) {
+            *self.bytes.entry(self.path.join("/")).or_default() += size.total_bytes();
+        }
+
+        fn enter_node(&mut self, name: &'static str) {
+            self.path.push(name);
+        }
+
+        fn exit_node(&mut self) {
+            self.path.pop();
+        }
+    }
+
+    let value = (vec![0u8; 16], BTreeSet::from([1u32, 2, 3]));
+    let mut context = Context::new();
+    context
+        .add_sink(Nodes::default())
+        .add_sink(TotalSize::zero());
+    value.size_of_with_context(&mut context);
+
+    // The default counting behavior is a sink too
+    assert_eq!(context.sink::<TotalSize>(), Some(&context.total_size()));
+
+    let nodes = context.remove_sink::<Nodes>().unwrap();
+    assert!(nodes.path.is_empty());
+    assert_eq!(nodes.bytes["Vec"], 16);
+    assert_eq!(
+        nodes.bytes["BTreeSet"],
+        estimate_btree_size::<u32, ()>(3).unwrap(),
+    );
+    assert!(context.sink::<Nodes>().is_none());
+    assert!(context.clone().sink::<TotalSize>().is_none());
+}
+
+#[test]
+fn recording_keeps_totals() {
+    // Contexts that don't record anything skip straight to their totals,
+    // which have to match the totals of contexts that record everything
+    let value = (
+        vec![String::from("value"); 8],
+        Some(Box::new(0u64)),
+        Rc::new(vec![0u8; 32]),
+    );
+    let recorded = size_with(&value, |ctx| {
+        ctx.record_tree().record_types().add_sink(TotalSize::zero())
+    });
+    assert_eq!(recorded.total_size(), value.size_of());
+    assert_eq!(recorded.sink::<TotalSize>(), Some(&value.size_of()));
+
+    // Both of them saturate when they overflow
+    let mut plain = Context::new();
+    plain
+        .add(usize::MAX)
+        .add(1)
+        .add_allocations(usize::MAX, 8)
+        .add_allocation(8);
+    let mut recorded = Context::new();
+    recorded.add_sink(TotalSize::zero());
+    recorded
+        .add(usize::MAX)
+        .add(1)
+        .add_allocations(usize::MAX, 8)
+        .add_allocation(8);
+    assert!(plain.has_overflowed() && recorded.has_overflowed());
+    assert_eq!(plain.total_size(), recorded.total_size());
+    assert_eq!(
+        plain.total_size(),
+        TotalSize::new(usize::MAX, 0, 0, usize::MAX),
+    );
+}
+
+#[test]
+fn merge() {
+    let shared = Arc::new(vec![0u8; 64]);
+    let (first, second) = ((shared.clone(), vec![1u8; 16]), (shared, String::from("a")));
+
+    let mut merged = Context::new();
+    merged.add_sink(TotalSize::zero()).record_types();
+    first.size_of_with_context(&mut merged);
+
+    let mut other = Context::new();
+    other.record_types();
+    second.size_of_with_context(&mut other);
+
+    let expected = merged.total_size() + other.total_size();
+    merged.merge(&other);
+    assert_eq!(merged.total_size(), expected);
+    assert_eq!(merged.sink::<TotalSize>(), Some(&expected));
+
+    // Both contexts counted the shared allocation, but its pointer is only
+    // recorded once
+    assert_eq!(merged.seen_pointers(), 1);
+    let types = merged.type_report();
+    assert_eq!(types.get(type_name::<String>()).unwrap().instances(), 1);
+    assert_eq!(types.get(type_name::<Vec<u8>>()).unwrap().instances(), 3);
+}
+
+#[test]
+fn limits() {
+    let nested = vec![vec![vec![0u8; 8]; 2]; 2];
+    let vec_bytes = size_of::<Vec<u8>>();
+
+    // Collections past the maximum depth still add their own bytes
+    let context = size_with(&nested, |ctx| ctx.set_max_depth(Some(0)));
+    assert_eq!(
+        context.total_size(),
+        TotalSize::new(vec_bytes * 3, 0, 0, 1)
+            .with_inline_bytes(vec_bytes)
+            .with_truncated_values(1),
+    );
+    assert!(context.is_truncated());
+    assert_eq!(context.depth(), 0);
+
+    let context = size_with(&nested, |ctx| ctx.set_max_depth(Some(1)));
+    assert_eq!(context.total_size().total_bytes(), vec_bytes * 7);
+    assert_eq!(context.total_size().truncated_values(), 2);
+    assert_eq!(
+        size_with(&nested, |ctx| ctx.set_max_depth(Some(3))).total_size(),
+        nested.size_of()
+    );
+
+    let boxed = Box::new(Box::new(Box::new(0u64)));
+    assert_eq!(
+        size_with(&boxed, |ctx| ctx.set_max_depth(Some(1))).total_size(),
+        TotalSize::new(size_of::<Box<u64>>() * 3, 0, 0, 2)
+            .with_inline_bytes(size_of::<Box<u64>>())
+            .with_truncated_values(1),
+    );
+
+    // Collections stop visiting their elements once the budget runs out
+    let strings = vec![String::from("0123456789012345"); 100];
+    let budget = size_of::<Vec<String>>() + size_of::<String>() * 100 + 16 * 10;
+    let size = size_with(&strings, |ctx| ctx.set_byte_budget(Some(budget))).total_size();
+    assert_eq!(size.total_bytes(), budget);
+    assert_eq!(size.distinct_allocations(), 11);
+    assert_eq!(size.truncated_values(), 90);
+
+    let context = size_with(&strings, |ctx| ctx.set_byte_budget(Some(usize::MAX)));
+    assert_eq!(context.total_size(), strings.size_of());
+    assert!(!context.is_truncated());
+
+    // Sampled collections also stop once the budget runs out, only the elements
+    // before the last sampled one are extrapolated
+    let budget = size_of::<Vec<String>>() + size_of::<String>() * 100 + 16 * 3;
+    let mut context = Context::new();
+    context
+        .set_sample_size(Some(10))
+        .set_byte_budget(Some(budget));
+    strings.size_of_with_context(&mut context);
+    let size = context.total_size();
+    assert!(size.truncated_values() > 0);
+    assert_eq!(
+        3 + size.estimated_bytes() / 16 + size.truncated_values(),
+        100
+    );
+    assert_eq!(
+        size.distinct_allocations(),
+        1 + 100 - size.truncated_values()
+    );
+
+    // Arrays are inline and don't count as a level
+    let arrays = vec![[Box::new(0u64), Box::new(0u64)]; 2];
+    let context = size_with(&arrays, |ctx| ctx.set_max_depth(Some(2)));
+    assert_eq!(context.total_size(), arrays.size_of());
+    assert!(!context.is_truncated());
+}
+
+#[test]
+fn reset() {
+    let shared = Arc::new(vec![0u8; 64]);
+    let value = (shared.clone(), shared);
+
+    let mut context = Context::new();
+    context.record_tree().record_types().add(usize::MAX).add(1);
+    assert!(context.has_overflowed());
+
+    context.reset();
+    assert!(!context.has_overflowed());
+    assert_eq!(context.seen_pointers(), 0);
+    assert!(context.is_recording_tree() && context.is_recording_types());
+
+    let mut fresh = Context::new();
+    fresh.record_tree().record_types();
+    value.size_of_with_context(&mut fresh);
+
+    // A reset context measures the same as a fresh one
+    for _ in 0..2 {
+        context.reset();
+        value.size_of_with_context(&mut context);
+        assert_eq!(context.total_size(), fresh.total_size());
+        assert_eq!(context.seen_pointers(), 1);
+        assert_eq!(context.size_report("value"), fresh.size_report("value"));
+        assert_eq!(context.type_report(), fresh.type_report());
+    }
+}
+
+#[test]
+fn pointer_snapshots() {
+    let shared = Arc::new(vec![0u8; 64]);
+    let (first, second) = (Arc::new(1u32), shared.clone());
+
+    let mut context = Context::new();
+    shared.size_of_with_context(&mut context);
+    let snapshot = context.pointer_snapshot();
+    assert_eq!(snapshot.len(), 1);
+
+    // Pointers seen since the snapshot are forgotten while the counts are kept
+    first.size_of_with_context(&mut context);
+    let total = context.total_size();
+    context.restore_pointers(&snapshot);
+    assert_eq!(context.total_size(), total);
+    assert_eq!(context.seen_pointers(), 1);
+    assert!(!context.contains_ptr(Arc::as_ptr(&first)));
+
+    // Pointers within the snapshot are still deduplicated
+    let before = context.total_size();
+    second.size_of_with_context(&mut context);
+    assert_eq!(
+        context.total_size(),
+        before + TotalSize::inline(size_of::<Arc<Vec<u8>>>()),
+    );
+}
+
+#[test]
+fn allocation_histogram() {
+    let value = (
+        vec![0u8; 100],
+        vec![Box::new(0u64), Box::new(1u64)],
+        Rc::new(0u8),
+    );
+
+    let mut context = Context::new();
+    context.record_allocation_histogram();
+    value.size_of_with_context(&mut context);
+    context.add_distinct_allocation();
+
+    let histogram = context.allocation_histogram();
+    assert_eq!(histogram.total_allocations(), 6);
+    assert_eq!(histogram.unknown_sizes(), 1);
+
+    // The two boxes, the vec holding them, the rc's allocation (two counts
+    // and a padded byte) and the byte vec
+    let buckets: Vec<_> = histogram
+        .buckets()
+        .map(|bucket| {
+            (
+                bucket.max_size(),
+                bucket.allocations(),
+                bucket.total_bytes(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        buckets,
+        [(8, 2, 16), (16, 1, 16), (32, 1, 24), (128, 1, 100)]
+    );
+    assert_eq!(histogram.bucket(24).min_size(), 17);
+
+    // Allocations are recorded with the same size as the bytes they hold
+    let value = (
+        alloc::ffi::CString::new("size").unwrap(),
+        Context::with_pointer_capacity(100),
+    );
+    let mut context = Context::new();
+    context.record_allocation_histogram();
+    value.size_of_with_context(&mut context);
+    let histogram_bytes = context
+        .allocation_histogram()
+        .buckets()
+        .map(|bucket| bucket.total_bytes())
+        .sum::<usize>();
+    assert_eq!(histogram_bytes, context.total_size().heap_bytes());
+    assert_eq!(context.allocation_histogram().total_allocations(), 2);
+
+    // Histograms are only recorded when requested
+    let mut context = Context::new();
+    value.size_of_with_context(&mut context);
+    assert!(context.allocation_histogram().is_empty());
+}
+
+#[test]
+fn overflow() {
+    let mut context = Context::new();
+    context.add(usize::MAX - 1).add(8);
+    assert!(context.has_overflowed());
+    assert_eq!(context.total_size().total_bytes(), usize::MAX);
+
+    // Capacities that can't be multiplied or are smaller than their length
+    let mut context = Context::new();
+    context.add_vectorlike(1, usize::MAX, 16);
+    assert!(context.has_overflowed());
+    assert_eq!(context.total_size().total_bytes(), usize::MAX);
+
+    let mut context = Context::new();
+    context.add_vectorlike(8, 4, 1);
+    assert!(context.has_overflowed());
+    assert_eq!(context.total_size(), TotalSize::total(4));
+
+    let mut context = Context::new();
+    context.add_vectorlike(4, 8, 1).add_arraylike(4, 1);
+    assert!(!context.has_overflowed());
+
+    // Allocations of corrupted capacities saturate along with their bytes
+    let mut context = Context::new();
+    context
+        .record_allocation_histogram()
+        .set_allocator_model(crate::allocator::GlibcMalloc)
+        .add_vectorlike_allocation(1, usize::MAX, 16);
+    assert!(context.has_overflowed());
+    assert_eq!(context.total_size().total_bytes(), usize::MAX);
+    assert_eq!(context.total_size().distinct_allocations(), 1);
+    assert_eq!(
+        context
+            .allocation_histogram()
+            .bucket(usize::MAX)
+            .total_bytes(),
+        usize::MAX,
+    );
+
+    let mut context = Context::new();
+    context.add_arraylike_allocation(usize::MAX, 2);
+    assert!(context.has_overflowed());
+    assert_eq!(context.total_size().distinct_allocations(), 1);
+
+    // Sizes saturate instead of overflowing
+    let max = TotalSize::total(usize::MAX);
+    let small = TotalSize::new(8, 4, 0, 1);
+    assert_eq!((max + small).total_bytes(), usize::MAX);
+    assert_eq!(small - max, TotalSize::new(0, 4, 0, 1));
+    assert_eq!(small.saturating_sub(max), small - max);
+    assert_eq!(small.checked_sub(max), None);
+    assert_eq!(max.checked_sub(small), None);
+    assert_eq!(
+        small.checked_sub(TotalSize::new(2, 2, 0, 1)),
+        Some(TotalSize::new(6, 2, 0, 0)),
+    );
+    assert_eq!(TotalSize::new(4, 8, 0, 0).used_bytes(), 0);
+}
+
 #[test]
 fn btree() {
     let empty_set = BTreeSet::<u32>::new();
     assert_eq!(
         empty_set.size_of(),
-        TotalSize::total(size_of::<BTreeSet<u32>>()),
+        TotalSize::inline(size_of::<BTreeSet<u32>>()),
     );
 
     let empty_map = BTreeMap::<u32, u32>::new();
     assert_eq!(
         empty_map.size_of(),
-        TotalSize::total(size_of::<BTreeMap<u32, u32>>()),
+        TotalSize::inline(size_of::<BTreeMap<u32, u32>>()),
     );
 
     let mut set = BTreeSet::<u32>::new();
@@ -231,11 +1026,13 @@ fn btree() {
     assert_eq!(
         set.size_of(),
         TotalSize::new(
-            size_of::<BTreeSet<u32>>() + estimate_btree_size::<u32, ()>(10),
+            size_of::<BTreeSet<u32>>() + estimate_btree_size::<u32, ()>(10).unwrap(),
             0,
             0,
             1,
-        ),
+        )
+        .with_estimated_bytes(estimate_btree_size::<u32, ()>(10).unwrap())
+        .with_inline_bytes(size_of::<BTreeSet<u32>>()),
     );
 
     let mut map = BTreeMap::<u32, u32>::new();
@@ -243,24 +1040,283 @@ fn btree() {
     assert_eq!(
         map.size_of(),
         TotalSize::new(
-            size_of::<BTreeMap<u32, u32>>() + estimate_btree_size::<u32, u32>(10),
+            size_of::<BTreeMap<u32, u32>>() + estimate_btree_size::<u32, u32>(10).unwrap(),
             0,
             0,
             1,
+        )
+        .with_estimated_bytes(estimate_btree_size::<u32, u32>(10).unwrap())
+        .with_inline_bytes(size_of::<BTreeMap<u32, u32>>()),
+    );
+}
+
+#[test]
+fn size_tree() {
+    let mut map = BTreeMap::new();
+    map.insert(String::from("key"), vec![0u8; 10]);
+
+    let vec = vec![map];
+    let mut report = vec.size_of_tree();
+    assert_eq!(report.total_size(), vec.size_of());
+
+    let outer = report.root().child("Vec").unwrap();
+    assert_eq!(
+        outer.size(),
+        vec.size_of() - TotalSize::inline(size_of::<Vec<()>>())
+    );
+
+    let map = outer.child("BTreeMap").unwrap();
+    assert_eq!(
+        map.child("keys").unwrap().size(),
+        TotalSize::new(3, 0, 0, 1)
+    );
+    assert_eq!(
+        map.child("values").unwrap().size(),
+        TotalSize::new(10, 0, 0, 1),
+    );
+
+    report.sort_by_total_bytes();
+    let names: Vec<_> = report
+        .iter()
+        .map(|(depth, node)| (depth, node.name()))
+        .collect();
+    assert_eq!(
+        &names[1..],
+        [
+            (1, "Vec"),
+            (2, "BTreeMap"),
+            (3, "values"),
+            (4, "Vec"),
+            (3, "keys"),
+        ],
+    );
+}
+
+#[test]
+fn folded_stacks() {
+    let mut map = BTreeMap::new();
+    map.insert(String::from("key"), vec![0u8; 10]);
+    let mut values = vec![map];
+    values.reserve_exact(1);
+
+    let mut context = Context::new();
+    context.record_tree();
+    values.size_of_with_context(&mut context);
+    let report = context.size_report("values");
+
+    // Every node only reports its own bytes
+    let (vec, map) = (size_of::<Vec<()>>(), size_of::<BTreeMap<String, Vec<u8>>>());
+    let folded = report.to_folded(ByteMetric::Total);
+    assert_eq!(
+        folded,
+        format!(
+            "values {vec}\n\
+             values;Vec {}\n\
+             values;Vec;BTreeMap {}\n\
+             values;Vec;BTreeMap;keys 3\n\
+             values;Vec;BTreeMap;values;Vec 10\n",
+            map * 2,
+            estimate_btree_size::<String, Vec<u8>>(1).unwrap(),
         ),
     );
+    let bytes = folded
+        .lines()
+        .map(|line| line.rsplit(' ').next().unwrap().parse::<usize>().unwrap())
+        .sum::<usize>();
+    assert_eq!(bytes, context.total_size().total_bytes());
+
+    assert_eq!(
+        report.to_folded(ByteMetric::Excess),
+        format!("values;Vec {map}\n"),
+    );
+    assert!(report
+        .to_folded(ByteMetric::Used)
+        .starts_with(&format!("values {vec}\nvalues;Vec {map}\n")));
+
+    // Separators within names are replaced
+    let node = SizeNode::new("[u8; 4]", TotalSize::total(4), Vec::new());
+    assert_eq!(
+        SizeReport::new(node).to_folded(ByteMetric::Total),
+        "[u8: 4] 4\n",
+    );
+}
+
+#[test]
+fn dot_graph() {
+    let shared = Arc::new(String::from("shared"));
+    let value = (shared.clone(), vec![shared.clone(), shared]);
+
+    let mut context = Context::new();
+    context.add_sink(DotSink::new());
+    value.size_of_with_context(&mut context);
+    let graph = context.remove_sink::<DotSink>().unwrap();
+
+    // The tuple, the arc's allocation, its string and the vec's buffer
+    assert_eq!(graph.len(), 4);
+    assert_eq!(graph.shared_nodes(), 1);
+    assert_eq!(graph.omitted_allocations(), 0);
+
+    // The arc's allocation is owned by both the tuple and the vec
+    let dot = graph.to_string();
+    let shared_node = dot
+        .lines()
+        .find(|line| line.contains("peripheries=2"))
+        .and_then(|line| line.split_whitespace().next())
+        .unwrap();
+    let owners = dot
+        .lines()
+        .filter(|line| line.ends_with(&format!("-> {shared_node};")))
+        .count();
+    assert_eq!(owners, 2);
+
+    // Allocations past the limit are merged into their owners
+    let nested = vec![vec![0u8; 4]; 8];
+    let mut context = Context::new();
+    context.add_sink(DotSink::with_max_nodes(4));
+    nested.size_of_with_context(&mut context);
+    let graph = context.remove_sink::<DotSink>().unwrap();
+    assert_eq!(graph.len(), 6);
+    assert_eq!(graph.omitted_allocations(), 4);
+    assert!(graph.to_string().contains("4 more allocations"));
+}
+
+// The golden file assumes 64-bit pointers
+#[test]
+#[cfg(target_pointer_width = "64")]
+fn openmetrics() {
+    let mut cache: Vec<u64> = Vec::with_capacity(8);
+    cache.extend([1, 2, 3, 4]);
+    let interned = Arc::new(String::from("interned"));
+    let names = vec![interned.clone(), interned];
+
+    let report = crate::component_sizes([
+        ("cache", &cache as &dyn DynSizeOf),
+        ("names \"interned\"", &names as &dyn DynSizeOf),
+        ("empty", &() as &dyn DynSizeOf),
+    ]);
+    assert_eq!(
+        report.to_openmetrics("service_memory"),
+        include_str!("golden/components.openmetrics"),
+    );
+}
+
+#[test]
+fn type_report() {
+    let strings = vec![String::from("0123"), String::from("01234567")];
+    let boxed = Box::new(strings);
+
+    let mut context = Context::new();
+    context.record_types();
+    boxed.size_of_with_context(&mut context);
+
+    let report = context.type_report();
+    assert_eq!(report.len(), 3);
+
+    let boxed_size = report.get(type_name::<Box<Vec<String>>>()).unwrap();
+    assert_eq!(boxed_size.instances(), 1);
+    assert_eq!(
+        boxed_size.size(),
+        TotalSize::new(size_of::<Box<()>>() + size_of::<Vec<String>>(), 0, 0, 1)
+            .with_inline_bytes(size_of::<Box<()>>()),
+    );
+
+    let vec_size = report.get(type_name::<Vec<String>>()).unwrap();
+    assert_eq!(
+        vec_size.size(),
+        TotalSize::new(size_of::<String>() * 2, 0, 0, 1),
+    );
+
+    let string_size = report.get(type_name::<String>()).unwrap();
+    assert_eq!(string_size.instances(), 2);
+    assert_eq!(string_size.size(), TotalSize::new(12, 0, 0, 2));
+
+    // The types using the most bytes come first
+    assert_eq!(report.top(1)[0].type_name(), type_name::<Vec<String>>());
+    assert_eq!(
+        report.iter().map(TypeSize::size).sum::<TotalSize>(),
+        context.total_size(),
+    );
+}
+
+#[test]
+fn references() {
+    let vec = vec![0u32; 16];
+    let refs = (&vec, &vec, &mut 10u64);
+    let inline = size_of::<(&Vec<u32>, &Vec<u32>, &mut u64)>();
+
+    // References aren't followed by default
+    assert_eq!(refs.size_of(), TotalSize::inline(inline));
+
+    let mut context = Context::new();
+    context.set_follow_references(true);
+    refs.size_of_with_context(&mut context);
+
+    // The vec is only recorded once and none of it is owned
+    assert_eq!(
+        context.total_size(),
+        TotalSize::inline(inline).with_borrowed_bytes(size_of::<Vec<u32>>() + 64 + 8),
+    );
+
+    // Borrowing a shared allocation doesn't stop its owner from counting it
+    let rc = Rc::new(vec![0u8; 1000]);
+    let pair = (&*rc, rc.clone());
+    let owned = pair.size_of();
+
+    let mut context = Context::new();
+    context.set_follow_references(true);
+    pair.size_of_with_context(&mut context);
+    let size = context.total_size();
+    assert_eq!(size.total_bytes(), owned.total_bytes());
+    assert_eq!(size.shared_bytes(), owned.shared_bytes());
+    assert_eq!(size.borrowed_bytes(), size_of::<Vec<u8>>() + 1000);
+
+    // Neither does borrowing the pointers that own it
+    let arc = Arc::new(vec![0u8; 1000]);
+    let pointers = (&rc, rc.clone(), &arc, arc.clone());
+    let owned = pointers.size_of();
+
+    let mut context = Context::new();
+    context.set_follow_references(true);
+    pointers.size_of_with_context(&mut context);
+    let size = context.total_size();
+    assert_eq!(size.total_bytes(), owned.total_bytes());
+    assert_eq!(size.shared_bytes(), owned.shared_bytes());
+    assert_eq!(size.distinct_allocations(), owned.distinct_allocations());
+    assert_eq!(
+        size.borrowed_bytes(),
+        size_of::<Rc<Vec<u8>>>() + size_of::<Arc<Vec<u8>>>() + owned.heap_bytes(),
+    );
 }
 
 // TODO: Test shared pointers
 
+#[cfg(feature = "std")]
+mod allocator;
+
+mod histogram;
+
+#[cfg(feature = "rayon")]
+mod parallel;
+
+mod pointer_set;
+
+mod sampling;
+
+#[cfg(feature = "serde")]
+mod serialization;
+
 #[cfg(feature = "std")]
 mod std {
-    use crate::{std_impls::hashmap::estimate_hashmap_size, SizeOf, TotalSize};
+    use crate::{
+        std_impls::hashmap::estimate_hashmap_size, Context, LockPolicy, SizeOf, TotalSize,
+    };
     use std::{
+        cell::RefCell,
         collections::{HashMap, HashSet},
         mem::size_of,
         net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
         str::FromStr,
+        sync::{Mutex, RwLock},
     };
 
     #[test]
@@ -273,7 +1329,10 @@ mod std {
     #[test]
     fn hashset() {
         let empty = HashSet::<u32>::new();
-        assert_eq!(empty.size_of(), TotalSize::total(size_of::<HashSet<u32>>()),);
+        assert_eq!(
+            empty.size_of(),
+            TotalSize::inline(size_of::<HashSet<u32>>()),
+        );
 
         let allocated = HashSet::<u32>::with_capacity(1024);
         let (total_bytes, used_bytes) = estimate_hashmap_size::<u32, ()>(0, 1024);
@@ -284,7 +1343,9 @@ mod std {
                 total_bytes - used_bytes,
                 0,
                 1,
-            ),
+            )
+            .with_estimated_bytes(total_bytes)
+            .with_inline_bytes(size_of::<HashSet<u32>>()),
         );
 
         // TODO: Set containing elements
@@ -295,7 +1356,7 @@ mod std {
         let empty = HashMap::<u32, u32>::new();
         assert_eq!(
             empty.size_of(),
-            TotalSize::total(size_of::<HashMap<u32, u32>>()),
+            TotalSize::inline(size_of::<HashMap<u32, u32>>()),
         );
 
         let allocated = HashMap::<u32, u32>::with_capacity(1024);
@@ -307,36 +1368,87 @@ mod std {
                 total_bytes - used_bytes,
                 0,
                 1,
-            ),
+            )
+            .with_estimated_bytes(total_bytes)
+            .with_inline_bytes(size_of::<HashMap<u32, u32>>()),
         );
 
         // TODO: Map containing elements
     }
 
+    #[test]
+    fn locks() {
+        fn try_lock(value: &impl SizeOf) -> TotalSize {
+            let mut context = Context::new();
+            context.set_lock_policy(LockPolicy::TryLock);
+            value.size_of_with_context(&mut context);
+            context.total_size()
+        }
+
+        let mutex = Mutex::new(vec![0u8; 16]);
+        let expected = TotalSize::new(size_of::<Mutex<Vec<u8>>>() + 16, 0, 0, 1)
+            .with_inline_bytes(size_of::<Mutex<Vec<u8>>>());
+        assert_eq!(mutex.size_of(), expected);
+        assert_eq!(try_lock(&mutex), expected);
+
+        // Locks held by the measuring thread are skipped instead of deadlocking
+        let guard = mutex.lock().unwrap();
+        assert_eq!(
+            try_lock(&mutex),
+            TotalSize::inline(size_of::<Mutex<Vec<u8>>>()).with_unmeasured_values(1),
+        );
+        drop(guard);
+
+        let rwlock = RwLock::new(vec![0u8; 16]);
+        let reader = rwlock.read().unwrap();
+        assert_eq!(try_lock(&rwlock).unmeasured_values(), 0);
+        drop(reader);
+
+        let writer = rwlock.write().unwrap();
+        assert_eq!(try_lock(&rwlock).unmeasured_values(), 1);
+        drop(writer);
+
+        // Mutably borrowed cells are skipped regardless of the policy
+        let cells = vec![RefCell::new(vec![0u8; 16]), RefCell::new(vec![0u8; 16])];
+        let _borrow = cells[0].borrow_mut();
+        let size = cells.size_of();
+        assert_eq!(size.unmeasured_values(), 1);
+        assert_eq!(size.distinct_allocations(), 2);
+
+        // Cells of childless values still record that they're borrowed
+        let cells = vec![RefCell::new(1u64)];
+        let _borrow = cells[0].borrow_mut();
+        assert_eq!(cells.size_of().unmeasured_values(), 1);
+
+        let rwlocks = vec![RwLock::new(1u64)];
+        let _writer = rwlocks[0].write().unwrap();
+        assert_eq!(try_lock(&rwlocks).unmeasured_values(), 1);
+    }
+
     #[test]
     fn socket_addresses() {
         let ipv4 = Ipv4Addr::new(127, 0, 0, 1);
-        assert_eq!(ipv4.size_of(), TotalSize::total(4));
+        assert_eq!(ipv4.size_of(), TotalSize::inline(4));
 
         let ipv6 = Ipv6Addr::from_str("::1").unwrap();
-        assert_eq!(ipv6.size_of(), TotalSize::total(16));
+        assert_eq!(ipv6.size_of(), TotalSize::inline(16));
 
         let mut ip = IpAddr::V4(ipv4);
-        assert_eq!(ip.size_of(), TotalSize::total(17));
+        assert_eq!(ip.size_of(), TotalSize::inline(17));
 
         ip = IpAddr::V6(ipv6);
-        assert_eq!(ip.size_of(), TotalSize::total(17));
+        assert_eq!(ip.size_of(), TotalSize::inline(17));
 
         let addrv4 = SocketAddrV4::new(ipv4, 8080);
-        assert_eq!(addrv4.size_of(), TotalSize::total(6));
+        assert_eq!(addrv4.size_of(), TotalSize::inline(6));
 
         let addrv6 = SocketAddrV6::new(ipv6, 1, 2, 3);
-        assert_eq!(addrv6.size_of(), TotalSize::total(28));
+        assert_eq!(addrv6.size_of(), TotalSize::inline(28));
 
         let mut addr = SocketAddr::V4(addrv4);
-        assert_eq!(addr.size_of(), TotalSize::total(32));
+        assert_eq!(addr.size_of(), TotalSize::inline(32));
 
         addr = SocketAddr::V6(addrv6);
-        assert_eq!(addr.size_of(), TotalSize::total(32));
+        assert_eq!(addr.size_of(), TotalSize::inline(32));
     }
 }
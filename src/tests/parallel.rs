@@ -0,0 +1,136 @@
+use crate::{Context, ParSizeOf, SizeOf};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::{collections::HashMap, format, string::String, sync::Arc, vec, vec::Vec};
+
+/// Measures `value` sequentially and in parallel with contexts recording
+/// everything, returning both contexts
+fn measure<T>(value: &T) -> (Context, Context)
+where
+    T: ParSizeOf + ?Sized,
+{
+    let (mut sequential, mut parallel) = (Context::new(), Context::new());
+    sequential.record_tree().record_types();
+    parallel.record_tree().record_types();
+
+    value.size_of_with_context(&mut sequential);
+    value.par_size_of_with_context(&mut parallel);
+    (sequential, parallel)
+}
+
+fn assert_same<T>(value: &T)
+where
+    T: ParSizeOf + ?Sized,
+{
+    let (sequential, parallel) = measure(value);
+    assert_eq!(parallel.total_size(), sequential.total_size());
+    assert_eq!(parallel.seen_pointers(), sequential.seen_pointers());
+    assert_eq!(
+        parallel.size_report("value"),
+        sequential.size_report("value")
+    );
+    assert_eq!(parallel.type_report(), sequential.type_report());
+    assert_eq!(value.par_size_of(), value.size_of());
+}
+
+#[test]
+fn vecs() {
+    let strings: Vec<String> = (0..10_000).map(|i| format!("{i:0>16}")).collect();
+    assert_same(&strings);
+    assert_same(strings.as_slice());
+
+    // Collections below the threshold are measured on the current thread
+    let strings: Vec<String> = (0..10).map(|i| format!("{i:0>16}")).collect();
+    assert_same(&strings);
+
+    let sharded: Vec<HashMap<u32, String>> = (0..4)
+        .map(|shard| (0..5_000).map(|i| (i, format!("{shard}{i}"))).collect())
+        .collect();
+    assert_same(&sharded);
+}
+
+#[test]
+fn hashmaps() {
+    let map: HashMap<u32, Vec<u8>> = (0..10_000).map(|i| (i, vec![0; i as usize % 64])).collect();
+    assert_same(&map);
+}
+
+#[test]
+fn shared_pointers() {
+    // Every allocation is shared between many elements spread across threads
+    let shared: Vec<Arc<Vec<u8>>> = (0..100).map(|i| Arc::new(vec![0; i])).collect();
+    let arcs: Vec<Arc<Vec<u8>>> = (0..10_000).map(|i| shared[i % 100].clone()).collect();
+    assert_same(&arcs);
+
+    // Pointers seen before measuring in parallel aren't counted again
+    let mut context = Context::new();
+    shared.size_of_with_context(&mut context);
+    let before = context.total_size();
+    arcs.par_size_of_children(&mut context);
+    assert_eq!(
+        context.total_size().total_bytes(),
+        before.total_bytes() + 10_000 * core::mem::size_of::<Arc<Vec<u8>>>(),
+    );
+    assert_eq!(context.seen_pointers(), 100);
+}
+
+/// Counts how many times it was measured within a forked context
+struct Forked<'a>(&'a AtomicUsize);
+
+impl SizeOf for Forked<'_> {
+    fn size_of_children(&self, context: &mut Context) {
+        if context.parallel_pointers.is_some() {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl ParSizeOf for Forked<'_> {}
+
+#[test]
+fn nested_collections_fork() {
+    // The outer vec is too small to fork, but the maps within it aren't
+    let forked = AtomicUsize::new(0);
+    let sharded: Vec<HashMap<u32, Forked<'_>>> = (0..4)
+        .map(|_| (0..5_000).map(|i| (i, Forked(&forked))).collect())
+        .collect();
+    sharded.par_size_of();
+    assert_eq!(forked.load(Ordering::Relaxed), 4 * 5_000);
+
+    // Small collections are measured on the current thread
+    let forked = AtomicUsize::new(0);
+    let small: Vec<Vec<Forked<'_>>> = (0..4)
+        .map(|_| (0..10).map(|_| Forked(&forked)).collect())
+        .collect();
+    small.par_size_of();
+    assert_eq!(forked.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+fn followed_references() {
+    // Every referenced value is referenced by many elements spread across
+    // threads, the arcs are only reachable through references
+    let strings: Vec<String> = (0..100).map(|i| format!("{i:0>16}")).collect();
+    let shared: Vec<Arc<Vec<u8>>> = (0..100).map(|i| Arc::new(vec![0; i])).collect();
+    let arcs: Vec<Arc<Vec<u8>>> = (0..10_000).map(|i| shared[i % 100].clone()).collect();
+    let refs: Vec<(&String, &Arc<Vec<u8>>)> =
+        (0..10_000).map(|i| (&strings[i % 100], &arcs[i])).collect();
+
+    let (mut sequential, mut parallel) = (Context::new(), Context::new());
+    sequential.set_follow_references(true);
+    parallel.set_follow_references(true);
+
+    // References followed before measuring in parallel aren't followed again
+    for context in [&mut sequential, &mut parallel] {
+        (&strings[0], &arcs[0]).size_of_with_context(context);
+    }
+
+    refs.size_of_with_context(&mut sequential);
+    refs.par_size_of_with_context(&mut parallel);
+    assert_eq!(parallel.total_size(), sequential.total_size());
+    assert!(parallel.total_size().borrowed_bytes() != 0);
+    assert_eq!(parallel.references.len(), sequential.references.len());
+    assert_eq!(
+        parallel.borrowed_pointers.len(),
+        sequential.borrowed_pointers.len(),
+    );
+}
@@ -0,0 +1,66 @@
+use size_of::SizeOf;
+#[cfg(not(feature = "derive"))]
+use size_of_derive::SizeOf;
+
+#[derive(SizeOf, Clone, Copy)]
+struct Point {
+    x: f64,
+    y: f64,
+    tag: Option<u8>,
+}
+
+#[derive(SizeOf)]
+struct Named {
+    point: Point,
+    name: String,
+}
+
+#[derive(SizeOf)]
+struct Skipped(Point, #[size_of(skip)] String);
+
+#[derive(SizeOf)]
+enum Shape {
+    Empty,
+    Circle { center: Point, radius: f32 },
+    Polygon(#[size_of(skip)] Vec<Point>),
+}
+
+#[derive(SizeOf)]
+enum Labeled {
+    Point(Point),
+    Label(String),
+}
+
+#[derive(SizeOf)]
+#[size_of(skip_all)]
+struct SkipAll {
+    _name: String,
+}
+
+// Unsized types can be childless as well
+#[derive(SizeOf)]
+struct Tail {
+    len: usize,
+    data: [u8],
+}
+
+#[derive(SizeOf)]
+struct Generic<T: ?Sized> {
+    len: usize,
+    value: T,
+}
+
+fn main() {
+    assert!(Point::IS_CHILDLESS);
+    assert!(!Named::IS_CHILDLESS);
+    assert!(Skipped::IS_CHILDLESS);
+    assert!(Shape::IS_CHILDLESS);
+    assert!(!Labeled::IS_CHILDLESS);
+    assert!(SkipAll::IS_CHILDLESS);
+    assert!(Tail::IS_CHILDLESS);
+    assert!(Generic::<u8>::IS_CHILDLESS);
+    assert!(Generic::<str>::IS_CHILDLESS);
+    assert!(!Generic::<String>::IS_CHILDLESS);
+    assert!(<[Point; 4]>::IS_CHILDLESS);
+    assert!(!<Vec<Point>>::IS_CHILDLESS);
+}
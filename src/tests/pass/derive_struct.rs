@@ -25,7 +25,7 @@ fn main() {
     assert_eq!(Zst.size_of(), TotalSize::zero());
 
     let tuple = TupleStruct(10, 10, false, Vec::with_capacity(200));
-    assert_eq!(tuple.size_of(), TotalSize::total(size_of::<TupleStruct>()));
+    assert_eq!(tuple.size_of(), TotalSize::inline(size_of::<TupleStruct>()));
 
     let bracket = BracketStruct {
         foo: 10,
@@ -35,6 +35,7 @@ fn main() {
     };
     assert_eq!(
         bracket.size_of(),
-        TotalSize::new(size_of::<BracketStruct>() + 200, 200, 0, 1),
+        TotalSize::new(size_of::<BracketStruct>() + 200, 200, 0, 1)
+            .with_inline_bytes(size_of::<BracketStruct>()),
     );
 }
@@ -16,16 +16,16 @@ enum Action {
 
 fn main() {
     let action1 = Action::Action1.size_of();
-    assert_eq!(action1, TotalSize::total(size_of::<Action>()));
+    assert_eq!(action1, TotalSize::inline(size_of::<Action>()));
 
     let action2 = Action::Action2.size_of();
-    assert_eq!(action2, TotalSize::total(size_of::<Action>()));
+    assert_eq!(action2, TotalSize::inline(size_of::<Action>()));
 
     let empty_injection = Action::InjectActions {
         injected_actions: Vec::new(),
     }
     .size_of();
-    assert_eq!(empty_injection, TotalSize::total(size_of::<Action>()));
+    assert_eq!(empty_injection, TotalSize::inline(size_of::<Action>()));
 
     let filled_injection = Action::InjectActions {
         injected_actions: vec![Action::Action1, Action::Action2],
@@ -33,6 +33,6 @@ fn main() {
     .size_of();
     assert_eq!(
         filled_injection,
-        TotalSize::new(size_of::<Action>() * 3, 0, 0, 1),
+        TotalSize::new(size_of::<Action>() * 3, 0, 0, 1).with_inline_bytes(size_of::<Action>()),
     );
 }
@@ -0,0 +1,59 @@
+use core::mem::size_of;
+use size_of::{SizeOf, TotalSize};
+#[cfg(not(feature = "derive"))]
+use size_of_derive::SizeOf;
+
+#[derive(SizeOf)]
+struct Service {
+    name: String,
+    cache: Vec<Entry>,
+    #[size_of(skip)]
+    skipped: Vec<u8>,
+}
+
+#[derive(SizeOf)]
+struct Entry(u32, Vec<u8>);
+
+#[derive(SizeOf)]
+enum Message {
+    Empty,
+    Text { r#type: String },
+}
+
+fn main() {
+    let service = Service {
+        name: String::from("service"),
+        cache: vec![Entry(0, vec![0; 10]), Entry(1, vec![0; 20])],
+        skipped: Vec::with_capacity(1000),
+    };
+
+    let report = service.size_of_tree();
+    assert_eq!(report.total_size(), service.size_of());
+    assert!(report.root().name().ends_with("Service"));
+
+    let name = report.root().child("name").unwrap();
+    assert_eq!(name.size(), TotalSize::new(7, 0, 0, 1));
+
+    let cache = report.root().child("cache").unwrap();
+    assert_eq!(
+        cache.size(),
+        TotalSize::new(size_of::<Entry>() * 2 + 30, 0, 0, 3),
+    );
+
+    // Every element of the vec is aggregated under a single node
+    let elements = cache.child("Vec").unwrap();
+    assert_eq!(elements.children().len(), 1);
+    assert_eq!(
+        elements.child("1").unwrap().size(),
+        TotalSize::new(30, 0, 0, 2),
+    );
+    assert!(report.root().child("skipped").is_none());
+
+    let message = Message::Text {
+        r#type: String::from("text"),
+    };
+    let report = message.size_of_tree();
+    let text = report.root().child("Text").unwrap();
+    assert_eq!(text.child("type").unwrap().size(), TotalSize::new(4, 0, 0, 1));
+    assert!(Message::Empty.size_of_tree().root().is_leaf());
+}
@@ -26,11 +26,11 @@ fn main() {
     };
     assert_eq!(
         container.size_of(),
-        TotalSize::total(size_of::<ContainsNonImplementer>()),
+        TotalSize::inline(size_of::<ContainsNonImplementer>()),
     );
 
     let union = Union {
         foo: ManuallyDrop::new(Vec::with_capacity(1000)),
     };
-    assert_eq!(union.size_of(), TotalSize::total(size_of::<Union>()));
+    assert_eq!(union.size_of(), TotalSize::inline(size_of::<Union>()));
 }
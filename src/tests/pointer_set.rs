@@ -0,0 +1,78 @@
+use crate::{pointer_set::PointerSet, SizeOf};
+use alloc::{collections::BTreeSet, vec::Vec};
+use core::mem::size_of;
+
+/// Returns the number of slots in the set's table
+fn slot_count(set: &PointerSet) -> usize {
+    set.size_of().heap_bytes() / size_of::<usize>()
+}
+
+#[test]
+fn insert_and_contains() {
+    let mut set = PointerSet::default();
+    assert_eq!(set.len(), 0);
+    assert!(!set.contains(0x1000));
+
+    assert!(set.insert(0x1000));
+    assert!(!set.insert(0x1000));
+    assert!(set.contains(0x1000));
+    assert!(!set.contains(0x1008));
+
+    // The null address is stored out of the table
+    assert!(!set.contains(0));
+    assert!(set.insert(0));
+    assert!(!set.insert(0));
+    assert!(set.contains(0));
+    assert_eq!(set.len(), 2);
+}
+
+#[test]
+fn matches_btree_set() {
+    let mut set = PointerSet::default();
+    let mut expected = BTreeSet::new();
+
+    // Aligned addresses with plenty of duplicates and collisions
+    for i in 0..10_000usize {
+        let addr = (i % 3_000) * 16 + (i % 7) * 0x10_0000;
+        assert_eq!(set.insert(addr), expected.insert(addr), "{addr:#x}");
+    }
+
+    assert_eq!(set.len(), expected.len());
+    assert!(expected.iter().all(|&addr| set.contains(addr)));
+
+    let mut addrs: Vec<_> = set.iter().collect();
+    addrs.sort_unstable();
+    assert!(addrs.into_iter().eq(expected.into_iter()));
+}
+
+#[test]
+fn clear() {
+    let mut set = PointerSet::default();
+    for addr in 0..100 {
+        set.insert(addr * 8);
+    }
+
+    let slots = slot_count(&set);
+    set.clear();
+    assert_eq!(set.len(), 0);
+    assert_eq!(slot_count(&set), slots);
+    assert!(!set.contains(0) && !set.contains(8));
+    assert!(set.insert(8));
+}
+
+#[test]
+fn with_capacity() {
+    let mut set = PointerSet::with_capacity(100);
+    let slots = slot_count(&set);
+    assert!(slots * 3 >= 100 * 4);
+
+    for addr in 1..=100 {
+        set.insert(addr * 8);
+    }
+    assert_eq!(slot_count(&set), slots);
+    assert_eq!(set.len(), 100);
+
+    // Capacities that can't exist are ignored
+    set.reserve(usize::MAX);
+    assert_eq!(slot_count(&set), slots);
+}
@@ -0,0 +1,12 @@
+use crate::sampling::sqrt;
+
+#[test]
+fn square_roots() {
+    assert_eq!(sqrt(0.0), 0.0);
+    assert_eq!(sqrt(-1.0), 0.0);
+    assert_eq!(sqrt(1.0), 1.0);
+    assert_eq!(sqrt(4.0), 2.0);
+    assert_eq!(sqrt(0.25), 0.5);
+    assert!((sqrt(2.0) - core::f64::consts::SQRT_2).abs() < 1e-12);
+    assert!((sqrt(1e18) - 1e9).abs() < 1e-3);
+}
@@ -0,0 +1,93 @@
+use crate::{sampling::SamplingStats, Context, DynSizeOf, HumanBytes, SizeOf, TotalSize};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{boxed::Box, collections::BTreeMap, fmt::Debug, string::String, vec};
+
+fn round_trip<T>(value: &T)
+where
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    let json = serde_json::to_string(value).unwrap();
+    assert_eq!(&serde_json::from_str::<T>(&json).unwrap(), value);
+}
+
+#[test]
+fn total_size() {
+    let size = (vec![0u8; 100], String::from("size")).size_of();
+    round_trip(&size);
+
+    // Field names are part of the format
+    let json = serde_json::to_value(TotalSize::new(8, 4, 2, 1)).unwrap();
+    assert_eq!(json["total_bytes"], 8);
+    assert_eq!(json["excess_bytes"], 4);
+    assert_eq!(json["shared_bytes"], 2);
+    assert_eq!(json["distinct_allocations"], 1);
+
+    // Snapshots missing newer counts are still readable
+    let old = r#"{"total_bytes":8,"excess_bytes":4,"shared_bytes":2,"distinct_allocations":1}"#;
+    assert_eq!(
+        serde_json::from_str::<TotalSize>(old).unwrap(),
+        TotalSize::new(8, 4, 2, 1),
+    );
+}
+
+#[test]
+fn human_bytes() {
+    assert_eq!(
+        serde_json::to_string(&HumanBytes::new(1024)).unwrap(),
+        "1024"
+    );
+    round_trip(&HumanBytes::new(1024));
+}
+
+#[test]
+fn reports() {
+    let mut map = BTreeMap::new();
+    map.insert(String::from("key"), vec![Box::new(0u64); 10]);
+
+    let mut context = Context::new();
+    context
+        .record_tree()
+        .record_types()
+        .record_allocation_histogram()
+        .set_sample_size(Some(4));
+    map.size_of_with_context(&mut context);
+
+    round_trip(&context.size_report("map"));
+    round_trip(&context.type_report());
+    round_trip(&context.sampling_report());
+
+    let histogram = context.allocation_histogram();
+    assert!(!histogram.is_empty());
+    round_trip(&histogram);
+    let json = serde_json::to_value(&histogram).unwrap();
+    assert_eq!(
+        json["buckets"].as_array().unwrap().len(),
+        histogram.buckets().count(),
+    );
+
+    let retained = crate::retained_sizes([("map", &map as &dyn DynSizeOf)]);
+    round_trip(&retained);
+    round_trip(&retained.diff(&crate::retained_sizes([("map", &0u8 as &dyn DynSizeOf)])));
+    round_trip(&crate::component_sizes([("map", &map as &dyn DynSizeOf)]));
+}
+
+#[test]
+fn sampling_reports() {
+    round_trip(&Context::new().sampling_report());
+
+    // A single sampled element has no variance
+    let mut context = Context::new();
+    context.set_sample_size(Some(1));
+    vec![String::from("a"), String::from("bc")].size_of_with_context(&mut context);
+    let report = context.sampling_report();
+    assert_eq!(report.sampled_elements(), 1);
+    assert_eq!(report.standard_error(), 0.0);
+    round_trip(&report);
+
+    // Variances that overflow are clamped so they can still be serialized
+    let mut stats = SamplingStats::default();
+    stats.record(usize::MAX, 2, 0.0, f64::MAX, 0);
+    let report = stats.report(0);
+    assert!(report.standard_error().is_finite());
+    round_trip(&report);
+}
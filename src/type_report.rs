@@ -0,0 +1,279 @@
+use crate::{Context, DiffReport, HumanBytes, SizeOf, TotalSize};
+use alloc::{borrow::Cow, collections::BTreeMap, string::ToString, vec::Vec};
+use core::{
+    fmt::{self, Display},
+    slice,
+};
+
+/// The memory usage of a program broken down by type, similar to the "top
+/// types" table of a heap profiler
+///
+/// Created by [`Context::type_report()`], entries are sorted so that the types
+/// using the most bytes come first
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TypeReport {
+    types: Vec<TypeSize>,
+}
+
+impl TypeReport {
+    /// Creates a new report from the given entries, sorting them so that the
+    /// types using the most bytes come first
+    #[inline]
+    pub fn new(mut types: Vec<TypeSize>) -> Self {
+        types.sort_by(|a, b| {
+            b.size
+                .total_bytes()
+                .cmp(&a.size.total_bytes())
+                .then_with(|| a.type_name.cmp(&b.type_name))
+        });
+
+        Self { types }
+    }
+
+    /// Returns the number of types within the report
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.types.len()
+    }
+
+    /// Returns `true` if the report contains no types
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty()
+    }
+
+    /// Returns the `n` types using the most bytes
+    #[inline]
+    pub fn top(&self, n: usize) -> &[TypeSize] {
+        &self.types[..n.min(self.types.len())]
+    }
+
+    /// Returns the entry for the type with the given name (if it exists)
+    #[inline]
+    pub fn get(&self, type_name: &str) -> Option<&TypeSize> {
+        self.types
+            .iter()
+            .find(|entry| entry.type_name() == type_name)
+    }
+
+    /// Returns an iterator over all types within the report
+    #[inline]
+    pub fn iter(&self) -> slice::Iter<'_, TypeSize> {
+        self.types.iter()
+    }
+
+    /// Compares the memory used by every type within the current report to
+    /// the same type within `after`
+    pub fn diff(&self, after: &Self) -> DiffReport {
+        let sizes = |report: &Self| -> Vec<_> {
+            report
+                .types
+                .iter()
+                .map(|entry| (entry.type_name.clone(), entry.size))
+                .collect()
+        };
+
+        DiffReport::new(sizes(self), sizes(after))
+    }
+}
+
+impl<'a> IntoIterator for &'a TypeReport {
+    type Item = &'a TypeSize;
+    type IntoIter = slice::Iter<'a, TypeSize>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl Display for TypeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>12} {:>12} {:>12} {:>12}  type",
+            "total", "excess", "allocations", "instances",
+        )?;
+
+        for entry in &self.types {
+            writeln!(
+                f,
+                "{:>12} {:>12} {:>12} {:>12}  {}",
+                HumanBytes::from(entry.size.total_bytes()).to_string(),
+                HumanBytes::from(entry.size.excess_bytes()).to_string(),
+                entry.size.distinct_allocations(),
+                entry.instances,
+                entry.type_name,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl SizeOf for TypeReport {
+    fn size_of_children(&self, context: &mut Context) {
+        self.types.size_of_children(context);
+    }
+}
+
+/// The memory attributed to a single type within a [`TypeReport`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TypeSize {
+    /// The name of the type, as given by [`core::any::type_name()`]
+    type_name: Cow<'static, str>,
+    /// The number of times a value of the type was measured
+    instances: usize,
+    /// The memory directly owned by values of the type
+    size: TotalSize,
+}
+
+impl TypeSize {
+    /// Creates a new `TypeSize`
+    #[inline]
+    pub fn new<N>(type_name: N, instances: usize, size: TotalSize) -> Self
+    where
+        N: Into<Cow<'static, str>>,
+    {
+        Self {
+            type_name: type_name.into(),
+            instances,
+            size,
+        }
+    }
+
+    /// Returns the name of the type
+    #[inline]
+    pub fn type_name(&self) -> &str {
+        &self.type_name
+    }
+
+    /// Returns the number of times a value of the type was measured
+    #[inline]
+    pub const fn instances(&self) -> usize {
+        self.instances
+    }
+
+    /// Returns the memory directly owned by values of the type, this doesn't
+    /// include memory owned by the type's children, e.g. the size of a
+    /// `Vec<String>` only includes the vec's buffer and not the strings' buffers
+    #[inline]
+    pub const fn size(&self) -> TotalSize {
+        self.size
+    }
+}
+
+impl SizeOf for TypeSize {
+    fn size_of_children(&self, context: &mut Context) {
+        self.type_name.size_of_children(context);
+    }
+}
+
+/// Aggregates the memory used by each type while a [`Context`] is traversing a
+/// value
+pub(crate) type TypeStats = SizeStats<&'static str>;
+
+/// Aggregates the memory directly used by each key while a [`Context`] is
+/// traversing a value, keys can be types or the addresses of shared allocations
+#[derive(Debug, Clone)]
+pub(crate) struct SizeStats<K> {
+    sizes: BTreeMap<K, (usize, TotalSize)>,
+    stack: Vec<Frame<K>>,
+}
+
+#[derive(Debug, Clone)]
+struct Frame<K> {
+    key: K,
+    /// The context's total size when the frame was entered
+    start: TotalSize,
+    /// Everything attributed to frames nested within this one
+    nested: TotalSize,
+}
+
+impl<K> SizeStats<K>
+where
+    K: Ord + Copy,
+{
+    /// Enters a value with the given key, returns `false` if the current value
+    /// is already being attributed to the same key
+    pub(crate) fn enter(&mut self, key: K, current: TotalSize) -> bool {
+        if self.stack.last().is_some_and(|frame| frame.key == key) {
+            return false;
+        }
+
+        self.stack.push(Frame {
+            key,
+            start: current,
+            nested: TotalSize::zero(),
+        });
+
+        true
+    }
+
+    /// Exits the current value, attributing everything recorded since it was
+    /// entered that wasn't attributed to a nested value to its key
+    pub(crate) fn exit(&mut self, current: TotalSize) {
+        if let Some(frame) = self.stack.pop() {
+            let delta = current - frame.start;
+            let (instances, size) = self.sizes.entry(frame.key).or_default();
+            *instances += 1;
+            *size += delta - frame.nested;
+
+            if let Some(parent) = self.stack.last_mut() {
+                parent.nested += delta;
+            }
+        }
+    }
+
+    /// Removes everything that was attributed
+    pub(crate) fn clear(&mut self) {
+        self.sizes.clear();
+        self.stack.clear();
+    }
+
+    /// Merges everything attributed by `other` into the current stats, all
+    /// of which is nested within the current value
+    pub(crate) fn merge(&mut self, other: &Self) {
+        let mut merged = TotalSize::zero();
+        for (&key, &(other_instances, other_size)) in &other.sizes {
+            let (instances, size) = self.sizes.entry(key).or_default();
+            *instances += other_instances;
+            *size += other_size;
+            merged += other_size;
+        }
+
+        if let Some(frame) = self.stack.last_mut() {
+            frame.nested += merged;
+        }
+    }
+
+    /// Returns an iterator over every key along with the number of times it
+    /// was entered and the memory attributed to it
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (K, usize, TotalSize)> + '_ {
+        self.sizes
+            .iter()
+            .map(|(&key, &(instances, size))| (key, instances, size))
+    }
+}
+
+impl<K> Default for SizeStats<K> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            sizes: BTreeMap::new(),
+            stack: Vec::new(),
+        }
+    }
+}
+
+impl TypeStats {
+    pub(crate) fn report(&self) -> TypeReport {
+        TypeReport::new(
+            self.iter()
+                .map(|(type_name, instances, size)| TypeSize::new(type_name, instances, size))
+                .collect(),
+        )
+    }
+}